@@ -0,0 +1,190 @@
+use std::{cell::RefCell, collections::HashMap, convert::TryFrom, rc::Rc};
+
+use super::parse::parse_extension_api;
+use crate::utils::{errors::ParseError, slice::Slice};
+
+/// Everything the engine exposes to scripts, as dumped by
+/// `godot --dump-extension-api`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtensionAPI {
+    pub version: String,
+    pub global_enums: HashMap<String, EngineEnum>,
+    pub utility_functions: HashMap<String, EngineMethod>,
+    pub builtin_classes: HashMap<String, BuiltinClass>,
+    pub classes: HashMap<String, EngineClass>,
+
+    /// Singleton name -> class name
+    pub singletons: HashMap<String, String>,
+}
+
+/// A Variant type like `Vector2` or `Array`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuiltinClass {
+    pub name: String,
+    pub indexing_return_type: Option<String>,
+    pub is_keyed: bool,
+
+    /// Member name -> type
+    pub members: HashMap<String, String>,
+
+    /// Constant name -> type
+    pub constants: HashMap<String, String>,
+    pub enums: HashMap<String, EngineEnum>,
+    pub operators: Vec<Operator>,
+    pub methods: HashMap<String, EngineMethod>,
+    pub constructors: Vec<Vec<EngineArgument>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operator {
+    /// The operator as written in GDScript (`+`, `==`, `in`...), or
+    /// `unary-`/`unary+` for the prefix forms
+    pub name: String,
+
+    /// `None` for unary operators
+    pub right_type: Option<String>,
+    pub return_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineClass {
+    pub name: String,
+    pub inherits: Option<String>,
+    pub is_refcounted: bool,
+    pub is_instantiable: bool,
+
+    /// Constant name -> value
+    pub constants: HashMap<String, i64>,
+    pub enums: HashMap<String, EngineEnum>,
+    pub methods: HashMap<String, EngineMethod>,
+    pub properties: HashMap<String, EngineProperty>,
+    pub signals: HashMap<String, EngineSignal>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineMethod {
+    pub name: String,
+
+    /// `None` for methods that don't return anything
+    pub return_type: Option<String>,
+    pub arguments: Vec<EngineArgument>,
+    pub is_const: bool,
+    pub is_static: bool,
+    pub is_vararg: bool,
+    pub is_virtual: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineArgument {
+    pub name: String,
+    pub arg_type: String,
+    pub default_value: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineProperty {
+    pub name: String,
+    pub prop_type: String,
+    pub getter: String,
+
+    /// `None` for read-only properties
+    pub setter: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineSignal {
+    pub name: String,
+    pub arguments: Vec<EngineArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineEnum {
+    pub name: String,
+    pub is_bitfield: bool,
+    pub values: Vec<(String, i64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JSONValue {
+    Null,
+    Boolean(bool),
+    Number(Slice),
+    String(Slice),
+    Array(Vec<JSONValue>),
+    Object {
+        src: Slice,
+        entries: Vec<(Slice, JSONValue)>,
+    },
+}
+
+const BUNDLED_API: &str = include_str!("extension_api.json");
+
+thread_local! {
+    static BUNDLED: RefCell<Option<Rc<ExtensionAPI>>> = const { RefCell::new(None) };
+}
+
+impl ExtensionAPI {
+    /// The API for the Godot version gdcheck ships with, used when a project
+    /// doesn't provide its own `extension_api.json`
+    pub fn bundled() -> Rc<ExtensionAPI> {
+        BUNDLED.with(|bundled| {
+            bundled
+                .borrow_mut()
+                .get_or_insert_with(|| {
+                    let code = Slice::new(Rc::new(BUNDLED_API.to_owned()));
+
+                    Rc::new(ExtensionAPI::try_from(code).unwrap())
+                })
+                .clone()
+        })
+    }
+
+    pub fn class(&self, name: &str) -> Option<&EngineClass> {
+        self.classes.get(name)
+    }
+
+    pub fn builtin_class(&self, name: &str) -> Option<&BuiltinClass> {
+        self.builtin_classes.get(name)
+    }
+
+    /// The value of a global enum member like `KEY_ESCAPE`
+    pub fn global_constant(&self, name: &str) -> Option<i64> {
+        self.global_enums.values().find_map(|engine_enum| {
+            engine_enum
+                .values
+                .iter()
+                .find(|(value_name, _)| value_name == name)
+                .map(|(_, value)| *value)
+        })
+    }
+}
+
+impl EngineClass {
+    /// Whether `name` is a constant or an enum member declared directly on
+    /// this class
+    pub fn has_constant(&self, name: &str) -> bool {
+        self.constant(name).is_some()
+    }
+
+    /// The value of a constant or enum member declared directly on this
+    /// class
+    pub fn constant(&self, name: &str) -> Option<i64> {
+        self.constants.get(name).copied().or_else(|| {
+            self.enums.values().find_map(|engine_enum| {
+                engine_enum
+                    .values
+                    .iter()
+                    .find(|(value_name, _)| value_name == name)
+                    .map(|(_, value)| *value)
+            })
+        })
+    }
+}
+
+impl TryFrom<Slice> for ExtensionAPI {
+    type Error = ParseError;
+
+    fn try_from(value: Slice) -> Result<Self, Self::Error> {
+        parse_extension_api(value)
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::bytes::complete::take_while;
+use nom::combinator::map;
+use nom::multi::separated_list0;
+use nom::sequence::{preceded, terminated, tuple};
+
+use crate::utils::errors::ParseError;
+use crate::utils::slice::Slice;
+use crate::utils::ParseResult;
+use crate::utils::RawParseError;
+use crate::utils::RawParseErrorDetails;
+use crate::utils::{number_literal, slice::Slicable};
+
+use super::ast::*;
+
+pub fn parse_extension_api(code: Slice) -> Result<ExtensionAPI, ParseError> {
+    let res = terminated(preceded(whitespace, parse_value), whitespace)(code.clone());
+
+    match res {
+        Ok((_, value)) => to_extension_api(&value),
+        Err(error) => Err(match error {
+            nom::Err::Error(RawParseError { src, details })
+            | nom::Err::Failure(RawParseError { src, details }) => ParseError {
+                module_id: None,
+                src,
+                message: match details {
+                    RawParseErrorDetails::Kind(kind) => kind.description().to_owned(),
+                    RawParseErrorDetails::Char(ch) => format!("Expected '{}'", ch),
+                },
+            },
+            nom::Err::Incomplete(_) => ParseError {
+                module_id: None,
+                src: code,
+                message: "Failed to parse".to_owned(),
+            },
+        }),
+    }
+}
+
+fn parse_value(i: Slice) -> ParseResult<JSONValue> {
+    alt((
+        map(parse_string, JSONValue::String),
+        map(parse_number, JSONValue::Number),
+        parse_array,
+        parse_object,
+        map(tag("true"), |_| JSONValue::Boolean(true)),
+        map(tag("false"), |_| JSONValue::Boolean(false)),
+        map(tag("null"), |_| JSONValue::Null),
+    ))(i)
+}
+
+/// The contents of a string literal, still escaped
+fn parse_string(i: Slice) -> ParseResult<Slice> {
+    let (i, _) = tag("\"")(i)?;
+
+    let mut escaping = false;
+    let end = i.as_str().char_indices().find_map(|(index, ch)| {
+        if escaping {
+            escaping = false;
+        } else if ch == '\\' {
+            escaping = true;
+        } else if ch == '"' {
+            return Some(index);
+        }
+
+        None
+    });
+
+    match end {
+        Some(end) => Ok((
+            i.clone().slice_range(end + 1, None),
+            i.slice_range(0, Some(end)),
+        )),
+        None => Err(nom::Err::Failure(RawParseError {
+            src: i,
+            details: RawParseErrorDetails::Char('"'),
+        })),
+    }
+}
+
+fn parse_number(i: Slice) -> ParseResult<Slice> {
+    map(
+        tuple((
+            number_literal,
+            take_while(|ch: char| ch.is_ascii_digit() || "eE+-".contains(ch)),
+        )),
+        |(number, exponent)| number.spanning(&exponent),
+    )(i)
+}
+
+fn parse_array(i: Slice) -> ParseResult<JSONValue> {
+    map(
+        tuple((
+            tag("["),
+            separated_list0(
+                preceded(whitespace, tag(",")),
+                preceded(whitespace, parse_value),
+            ),
+            preceded(whitespace, tag("]")),
+        )),
+        |(_, values, _)| JSONValue::Array(values),
+    )(i)
+}
+
+fn parse_object(i: Slice) -> ParseResult<JSONValue> {
+    map(
+        tuple((
+            tag("{"),
+            separated_list0(
+                preceded(whitespace, tag(",")),
+                preceded(
+                    whitespace,
+                    tuple((
+                        parse_string,
+                        preceded(whitespace, tag(":")),
+                        preceded(whitespace, parse_value),
+                    )),
+                ),
+            ),
+            preceded(whitespace, tag("}")),
+        )),
+        |(open, entries, close)| JSONValue::Object {
+            src: open.spanning(&close),
+            entries: entries
+                .into_iter()
+                .map(|(key, _, value)| (key, value))
+                .collect(),
+        },
+    )(i)
+}
+
+fn whitespace(i: Slice) -> ParseResult<()> {
+    map(
+        take_while(|c: char| c == ' ' || c == '\n' || c == '\t' || c == '\r'),
+        |_| (),
+    )(i)
+}
+
+impl JSONValue {
+    fn get(&self, key: &str) -> Option<&JSONValue> {
+        match self {
+            JSONValue::Object { src: _, entries } => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key.as_str() == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn get_str(&self, key: &str) -> Option<String> {
+        match self.get(key) {
+            Some(JSONValue::String(s)) => Some(unescape(s.as_str())),
+            _ => None,
+        }
+    }
+
+    fn get_bool(&self, key: &str) -> bool {
+        matches!(self.get(key), Some(JSONValue::Boolean(true)))
+    }
+
+    fn get_array(&self, key: &str) -> &[JSONValue] {
+        match self.get(key) {
+            Some(JSONValue::Array(values)) => values.as_slice(),
+            _ => &[],
+        }
+    }
+
+    /// Like `get_str()`, but the field has to be present
+    fn expect_str(&self, key: &str) -> Result<String, ParseError> {
+        self.get_str(key).ok_or_else(|| ParseError {
+            module_id: None,
+            src: match self {
+                JSONValue::Object { src, entries: _ } => src.clone(),
+                _ => Slice::new(Default::default()),
+            },
+            message: format!("Expected a string field '{}'", key),
+        })
+    }
+
+    fn expect_int(&self, key: &str) -> Result<i64, ParseError> {
+        match self.get(key) {
+            Some(JSONValue::Number(n)) => n.as_str().parse().map_err(|_| ParseError {
+                module_id: None,
+                src: n.clone(),
+                message: format!("Expected field '{}' to be an integer", key),
+            }),
+            _ => Err(ParseError {
+                module_id: None,
+                src: match self {
+                    JSONValue::Object { src, entries: _ } => src.clone(),
+                    _ => Slice::new(Default::default()),
+                },
+                message: format!("Expected a number field '{}'", key),
+            }),
+        }
+    }
+}
+
+fn unescape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('n') => res.push('\n'),
+                Some('t') => res.push('\t'),
+                Some('r') => res.push('\r'),
+                Some('b') => res.push('\u{8}'),
+                Some('f') => res.push('\u{c}'),
+                Some('u') => res.push(unescape_unicode(&mut chars)),
+                Some(other) => res.push(other),
+                None => {}
+            }
+        } else {
+            res.push(ch);
+        }
+    }
+
+    res
+}
+
+/// The character spelled by a `\uXXXX` escape, whose `\u` has already
+/// been consumed from `chars`. Characters outside the Basic Multilingual
+/// Plane are spelled as a surrogate pair of two escapes. Anything that
+/// doesn't spell a character becomes U+FFFD.
+fn unescape_unicode(chars: &mut std::str::Chars) -> char {
+    let hex = |chars: &mut std::str::Chars| {
+        let digits: String = chars.by_ref().take(4).collect();
+
+        if digits.len() == 4 && digits.chars().all(|digit| digit.is_ascii_hexdigit()) {
+            u32::from_str_radix(&digits, 16).ok()
+        } else {
+            None
+        }
+    };
+
+    let unit = match hex(chars) {
+        Some(unit) => unit,
+        None => return char::REPLACEMENT_CHARACTER,
+    };
+
+    if (0xD800..0xDC00).contains(&unit) {
+        let mut rest = chars.clone();
+        let low = match (rest.next(), rest.next()) {
+            (Some('\\'), Some('u')) => hex(&mut rest).filter(|low| (0xDC00..0xE000).contains(low)),
+            _ => None,
+        };
+
+        if let Some(low) = low {
+            *chars = rest;
+
+            return char::from_u32(0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00))
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+        }
+    }
+
+    // lone surrogates aren't characters
+    char::from_u32(unit).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+fn by_name<T, F: Fn(&JSONValue) -> Result<T, ParseError>>(
+    values: &[JSONValue],
+    convert: F,
+) -> Result<HashMap<String, T>, ParseError> {
+    values
+        .iter()
+        .map(|value| Ok((value.expect_str("name")?, convert(value)?)))
+        .collect()
+}
+
+fn to_extension_api(json: &JSONValue) -> Result<ExtensionAPI, ParseError> {
+    Ok(ExtensionAPI {
+        version: json
+            .get("header")
+            .and_then(|header| header.get_str("version_full_name"))
+            .unwrap_or_default(),
+        global_enums: by_name(json.get_array("global_enums"), to_enum)?,
+        utility_functions: by_name(json.get_array("utility_functions"), to_method)?,
+        builtin_classes: by_name(json.get_array("builtin_classes"), to_builtin_class)?,
+        classes: by_name(json.get_array("classes"), to_class)?,
+        singletons: by_name(json.get_array("singletons"), |singleton| {
+            singleton.expect_str("type")
+        })?,
+    })
+}
+
+fn to_builtin_class(json: &JSONValue) -> Result<BuiltinClass, ParseError> {
+    Ok(BuiltinClass {
+        name: json.expect_str("name")?,
+        indexing_return_type: json.get_str("indexing_return_type"),
+        is_keyed: json.get_bool("is_keyed"),
+        members: by_name(json.get_array("members"), |member| {
+            member.expect_str("type")
+        })?,
+        constants: by_name(json.get_array("constants"), |constant| {
+            constant.expect_str("type")
+        })?,
+        enums: by_name(json.get_array("enums"), to_enum)?,
+        operators: json
+            .get_array("operators")
+            .iter()
+            .map(|operator| {
+                Ok(Operator {
+                    name: operator.expect_str("name")?,
+                    right_type: operator.get_str("right_type"),
+                    return_type: operator.expect_str("return_type")?,
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        methods: by_name(json.get_array("methods"), to_method)?,
+        constructors: json
+            .get_array("constructors")
+            .iter()
+            .map(|constructor| to_arguments(constructor.get_array("arguments")))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn to_class(json: &JSONValue) -> Result<EngineClass, ParseError> {
+    Ok(EngineClass {
+        name: json.expect_str("name")?,
+        inherits: json.get_str("inherits"),
+        is_refcounted: json.get_bool("is_refcounted"),
+        is_instantiable: json.get_bool("is_instantiable"),
+        constants: by_name(json.get_array("constants"), |constant| {
+            constant.expect_int("value")
+        })?,
+        enums: by_name(json.get_array("enums"), to_enum)?,
+        methods: by_name(json.get_array("methods"), to_method)?,
+        properties: by_name(json.get_array("properties"), |property| {
+            Ok(EngineProperty {
+                name: property.expect_str("name")?,
+                prop_type: property.expect_str("type")?,
+                getter: property.get_str("getter").unwrap_or_default(),
+                setter: property
+                    .get_str("setter")
+                    .filter(|setter| !setter.is_empty()),
+            })
+        })?,
+        signals: by_name(json.get_array("signals"), |signal| {
+            Ok(EngineSignal {
+                name: signal.expect_str("name")?,
+                arguments: to_arguments(signal.get_array("arguments"))?,
+            })
+        })?,
+    })
+}
+
+fn to_method(json: &JSONValue) -> Result<EngineMethod, ParseError> {
+    Ok(EngineMethod {
+        name: json.expect_str("name")?,
+        // builtin classes and utility functions use "return_type", engine
+        // classes use "return_value"
+        return_type: json.get_str("return_type").or_else(|| {
+            json.get("return_value")
+                .and_then(|return_value| return_value.get_str("type"))
+        }),
+        arguments: to_arguments(json.get_array("arguments"))?,
+        is_const: json.get_bool("is_const"),
+        is_static: json.get_bool("is_static"),
+        is_vararg: json.get_bool("is_vararg"),
+        is_virtual: json.get_bool("is_virtual"),
+    })
+}
+
+fn to_arguments(json: &[JSONValue]) -> Result<Vec<EngineArgument>, ParseError> {
+    json.iter()
+        .map(|argument| {
+            Ok(EngineArgument {
+                name: argument.expect_str("name")?,
+                arg_type: argument.expect_str("type")?,
+                default_value: argument.get_str("default_value"),
+            })
+        })
+        .collect()
+}
+
+fn to_enum(json: &JSONValue) -> Result<EngineEnum, ParseError> {
+    Ok(EngineEnum {
+        name: json.expect_str("name")?,
+        is_bitfield: json.get_bool("is_bitfield"),
+        values: json
+            .get_array("values")
+            .iter()
+            .map(|value| Ok((value.expect_str("name")?, value.expect_int("value")?)))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+#[test]
+fn bundled_api() {
+    let api = ExtensionAPI::bundled();
+
+    let timer = api.class("Timer").unwrap();
+    assert_eq!(timer.inherits.as_deref(), Some("Node"));
+    assert_eq!(timer.properties["time_left"].setter, None);
+    assert_eq!(
+        timer.properties["wait_time"].setter.as_deref(),
+        Some("set_wait_time")
+    );
+    assert!(timer.signals.contains_key("timeout"));
+    assert!(timer.has_constant("TIMER_PROCESS_IDLE"));
+
+    let vector2 = api.builtin_class("Vector2").unwrap();
+    assert_eq!(vector2.members["x"], "float");
+    assert_eq!(
+        vector2.methods["normalized"].return_type.as_deref(),
+        Some("Vector2")
+    );
+
+    assert_eq!(
+        api.utility_functions["randi_range"].return_type.as_deref(),
+        Some("int")
+    );
+    assert_eq!(api.singletons["Input"], "Input");
+    assert_eq!(api.global_constant("KEY_ESCAPE"), Some(4194305));
+
+    // the whole engine, not just the commonly used parts
+    assert_eq!(api.version, "Godot Engine v4.2.stable.official");
+    assert_eq!(api.classes.len(), 876);
+    assert!(api.class("GPUParticles2D").is_some());
+    assert!(api.class("Label3D").is_some());
+    let node = api.class("Node").unwrap();
+    assert!(node.properties.contains_key("process_thread_group"));
+    assert!(node.methods.contains_key("set_process_unhandled_key_input"));
+
+    let code = "extends Node\nfunc f():\n\tvar particles := GPUParticles2D.new()\n\tvar label: Label3D = null\n\tprocess_thread_group = PROCESS_THREAD_GROUP_SUB_THREAD\n\tset_process_unhandled_key_input(true)\n";
+    let errors = crate::gdscript::check::check_source(code);
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn escapes_and_numbers() {
+    let code = r#"{
+        "header": { "version_full_name": "Godot \"Test\"" },
+        "global_enums": [
+            { "name": "Small", "is_bitfield": false, "values": [{ "name": "NEG", "value": -3 }] }
+        ]
+    }"#;
+    let api = parse_extension_api(Slice::new(std::rc::Rc::new(code.to_owned()))).unwrap();
+
+    assert_eq!(api.version, "Godot \"Test\"");
+    assert_eq!(api.global_constant("NEG"), Some(-3));
+}
+
+#[test]
+fn string_escapes() {
+    assert_eq!(unescape(r#"a\"b\\c\/d"#), "a\"b\\c/d");
+    assert_eq!(unescape(r"\b\f\n\r\t"), "\u{8}\u{c}\n\r\t");
+    assert_eq!(unescape(r"\u00e9\u00E9\u4e2d"), "éé中");
+    assert_eq!(unescape(r"\ud83d\ude00!"), "😀!");
+
+    // broken escapes don't take anything after them down with them
+    assert_eq!(unescape(r"\ud83d!"), "\u{FFFD}!");
+    assert_eq!(unescape(r"\ude00\ud83d\u0041"), "\u{FFFD}\u{FFFD}A");
+    assert_eq!(unescape(r"\u12"), "\u{FFFD}");
+}
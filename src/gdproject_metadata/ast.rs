@@ -19,6 +19,91 @@ impl GDProjectMetadata {
             other_sections: HashMap::new(),
         }
     }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.other_sections
+            .iter()
+            .find(|(section_name, _)| section_name.as_str() == name)
+            .map(|(_, section)| section)
+    }
+
+    /// The scripts and scenes listed under `[autoload]`, in the order
+    /// they're listed (which is the order the engine loads them in)
+    pub fn autoloads(&self) -> Vec<Autoload> {
+        let mut entries: Vec<(&Slice, &EntryValue)> = self
+            .section("autoload")
+            .map(|section| section.iter().collect())
+            .unwrap_or_default();
+
+        entries.sort_by_key(|(name, _)| name.start);
+        entries
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let path = value.as_string()?;
+                let path = path.as_str();
+
+                // a leading `*` makes the autoload a global singleton
+                let (path, enabled) = match path.strip_prefix('*') {
+                    Some(path) => (path, true),
+                    None => (path, false),
+                };
+
+                Some(Autoload {
+                    name: name.as_str().to_owned(),
+                    path: path.to_owned(),
+                    enabled,
+                })
+            })
+            .collect()
+    }
+
+    /// The global script classes listed in Godot 3's `project.godot`, or
+    /// Godot 4's `.godot/global_script_class_cache.cfg` (which is parsed
+    /// the same way). `None` if this file doesn't list them.
+    pub fn global_script_classes(&self) -> Option<Vec<GlobalScriptClass>> {
+        let list = self
+            .front_section
+            .iter()
+            .find(|(key, _)| matches!(key.as_str(), "_global_script_classes" | "list"))
+            .map(|(_, list)| list)?;
+
+        let entries = match list {
+            EntryValue::ListValue(entries) => entries,
+            // Godot 4 writes out a typed array
+            EntryValue::ConstructedValue { class: _, entries } => match entries.as_slice() {
+                [EntryValue::ListValue(entries)] => entries,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(
+            entries
+                .iter()
+                .filter_map(|entry| {
+                    Some(GlobalScriptClass {
+                        base: entry.get("base")?.as_string()?,
+                        class: entry.get("class")?.as_string()?,
+                        language: entry.get("language")?.as_string()?,
+                        path: entry.get("path")?.as_string()?,
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+/// An entry in the `[autoload]` section, like
+/// `GameState="*res://game_state.gd"`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Autoload {
+    pub name: String,
+
+    /// The `res://` path of the script or scene, without the `*`
+    pub path: String,
+
+    /// Whether the autoload is accessible as a global by its name
+    pub enabled: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -48,6 +133,29 @@ pub enum EntryValue {
     },
 }
 
+impl EntryValue {
+    /// The contents of a string value, without its quotes
+    pub fn as_string(&self) -> Option<Slice> {
+        match self {
+            EntryValue::StringValue { s, ampersand: _ } if s.len() >= 2 => {
+                Some(s.clone().slice_range(1, Some(s.len() - 1)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The value stored under `key` in a dictionary value
+    pub fn get(&self, key: &str) -> Option<&EntryValue> {
+        match self {
+            EntryValue::DictValue(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key.as_str().trim_matches('"') == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct GlobalScriptClass {
     pub base: Slice,
@@ -67,6 +67,16 @@ pub fn parse_gdproject_metadata(code: Slice) -> Result<GDProjectMetadata, ParseE
                 }
             }
 
+            // the last section doesn't have another one after it to close it
+            match current_section_name {
+                Some(current_section_name) => {
+                    project
+                        .other_sections
+                        .insert(current_section_name, current_section_entries);
+                }
+                None => project.front_section = current_section_entries,
+            }
+
             Ok(project)
             // }
         }
@@ -128,7 +138,7 @@ fn parse_key_and_value(i: Slice) -> ParseResult<(Slice, EntryValue)> {
 }
 
 fn parse_key(i: Slice) -> ParseResult<Slice> {
-    take_while1(|ch: char| ch.is_alphanumeric() || ch == '_' || ch == '/')(i)
+    take_while1(|ch: char| ch.is_alphanumeric() || ch == '_' || ch == '/' || ch == '.')(i)
 }
 
 fn parse_value(i: Slice) -> ParseResult<EntryValue> {
@@ -220,11 +230,20 @@ fn parse_constructed(i: Slice) -> ParseResult<ConstructedValue> {
     map(
         tuple((
             parse_key,
+            // typed arrays, like `Array[Dictionary]([...])`
+            opt(tuple((
+                tag("["),
+                preceded(whitespace_and_comments, parse_key),
+                preceded(whitespace_and_comments, tag("]")),
+            ))),
             preceded(whitespace_and_comments, tag("(")),
-            separated_list1(preceded(whitespace_and_comments, tag(",")), parse_value),
+            separated_list1(
+                preceded(whitespace_and_comments, tag(",")),
+                preceded(whitespace_and_comments, parse_value),
+            ),
             preceded(whitespace_and_comments, tag(")")),
         )),
-        |(class, _, entries, _)| ConstructedValue { class, entries },
+        |(class, _, _, entries, _)| ConstructedValue { class, entries },
     )(i)
 }
 
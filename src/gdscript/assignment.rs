@@ -0,0 +1,163 @@
+use super::{
+    ast::*,
+    gd_type::Type,
+    infer_type::{
+        class_name_of, global_identifier_type, member_type, self_type, subscript_type,
+        variable_type, InferTypeContext,
+    },
+    resolve_type::resolve_api_type,
+};
+
+/// What an assignment to some expression stores into
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssignmentTarget {
+    /// A location that only holds values of a certain type
+    Typed(Type),
+
+    /// A dynamically-typed location, which takes anything
+    Untyped,
+
+    /// Something that can't be assigned to, and the reason why
+    NotAssignable(String),
+}
+
+impl AST<Expression> {
+    /// What assigning to this expression stores into
+    pub fn assignment_target<'a>(&self, ctx: InferTypeContext<'a>) -> AssignmentTarget {
+        match self.downcast() {
+            Expression::LocalIdentifier(LocalIdentifier { name }) => {
+                let name = name.as_str();
+
+                if let Some(declaration) = self.resolve_symbol(name) {
+                    return declaration_target(&declaration, name, ctx);
+                }
+
+                // members inherited from the script's base classes
+                if let Some(target) = member_target(&self_type(self, ctx), name, ctx) {
+                    return target;
+                }
+
+                match global_identifier_type(self, name, ctx) {
+                    Some(_) => {
+                        AssignmentTarget::NotAssignable(format!("Cannot assign to '{}'", name))
+                    }
+                    // undeclared, which has already been reported
+                    None => AssignmentTarget::Typed(Type::Poisoned),
+                }
+            }
+            Expression::PropertyAccess(PropertyAccess { subject, property }) => {
+                let property = property.downcast().name;
+                let property = property.as_str();
+
+                // like `Vector2.ZERO` or `State.IDLE`
+                if subject.named_enum().is_some() || subject.resolve_as_type(ctx.into()).is_some() {
+                    return AssignmentTarget::NotAssignable(format!(
+                        "Cannot assign to constant '{}'",
+                        property
+                    ));
+                }
+
+                let subject_type = subject.infer_type(ctx);
+
+                member_target(&subject_type, property, ctx)
+                    .unwrap_or_else(|| typed(member_type(&subject_type, property, ctx)))
+            }
+            Expression::Subscript(Subscript { subject, index }) => typed(
+                subscript_type(&subject.infer_type(ctx), &index.infer_type(ctx), ctx)
+                    .unwrap_or(Type::Poisoned),
+            ),
+            _ => AssignmentTarget::NotAssignable(String::from("Cannot assign to this expression")),
+        }
+    }
+}
+
+fn typed(typ: Type) -> AssignmentTarget {
+    match typ {
+        Type::Any => AssignmentTarget::Untyped,
+        typ => AssignmentTarget::Typed(typ),
+    }
+}
+
+/// Assigning to the name bound by `declaration`
+fn declaration_target<'a>(
+    declaration: &ASTAny,
+    name: &str,
+    ctx: InferTypeContext<'a>,
+) -> AssignmentTarget {
+    let kind = match declaration.details() {
+        Any::ValueDeclaration(ValueDeclaration {
+            is_const: false,
+            name: _,
+            declared_type: _,
+            is_inferred: _,
+            value: _,
+        })
+        | Any::FuncArg(_)
+        | Any::ForLoop(_)
+        | Any::BindingPattern(_) => {
+            return variable_type(declaration, ctx).map_or(AssignmentTarget::Untyped, typed)
+        }
+        Any::ValueDeclaration(_) | Any::EnumDeclaration(_) => "constant",
+        Any::FuncDeclaration(_) => "function",
+        Any::SignalDeclaration(_) => "signal",
+        Any::ClassDeclaration(_) => "class",
+        _ => return AssignmentTarget::NotAssignable(format!("Cannot assign to '{}'", name)),
+    };
+
+    AssignmentTarget::NotAssignable(format!("Cannot assign to {} '{}'", kind, name))
+}
+
+/// Assigning to the member `name` of a value of type `typ`, if it's a class
+/// that has such a member
+fn member_target<'a>(
+    typ: &Type,
+    name: &str,
+    ctx: InferTypeContext<'a>,
+) -> Option<AssignmentTarget> {
+    let godot_project = ctx.godot_project;
+    let class = class_name_of(typ)?;
+
+    if let Some(declaration) =
+        godot_project.find_in_script_classes(class, |script| script.declaration(name))
+    {
+        return Some(declaration_target(&declaration, name, ctx));
+    }
+
+    godot_project.find_in_engine_classes(class, |engine_class| {
+        let kind = if let Some(property) = engine_class.properties.get(name) {
+            let setter = match &property.setter {
+                Some(setter) => setter,
+                None => {
+                    return Some(AssignmentTarget::NotAssignable(format!(
+                        "Property '{}' of {} is read-only",
+                        name, engine_class.name
+                    )))
+                }
+            };
+
+            // the setter decides what's accepted, which can be more than
+            // the property's own type
+            let setter_type = engine_class
+                .methods
+                .get(setter)
+                .and_then(|setter| setter.arguments.last())
+                .map(|argument| argument.arg_type.as_str())
+                .unwrap_or(&property.prop_type);
+
+            return Some(typed(resolve_api_type(setter_type, ctx.into())));
+        } else if engine_class.methods.contains_key(name) {
+            "method"
+        } else if engine_class.signals.contains_key(name) {
+            "signal"
+        } else if engine_class.constant(name).is_some() || engine_class.enums.contains_key(name) {
+            "constant"
+        } else {
+            return None;
+        };
+
+        Some(AssignmentTarget::NotAssignable(format!(
+            "Cannot assign to {} '{}'",
+            kind, name
+        )))
+    })
+}
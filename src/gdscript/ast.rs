@@ -1,13 +1,15 @@
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::{
-    cell::RefCell,
+    cell::{OnceCell, RefCell},
     rc::{Rc, Weak},
 };
 use strum_macros::{EnumString, IntoStaticStr};
 
 use crate::utils::slice::{Slicable, Slice};
 
+use super::scope::Scope;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ModuleID(pub Rc<String>);
 
@@ -85,6 +87,17 @@ where
         &self.0.details
     }
 
+    /// The symbols this node declares for its descendants, if it
+    /// introduces a lexical scope
+    pub fn scope(&self) -> Option<&Scope> {
+        self.0.scope.get()
+    }
+
+    pub fn set_scope(&self, scope: Scope) {
+        // scopes are built once, right after parsing
+        let _ = self.0.scope.set(scope);
+    }
+
     pub fn ptr_eq<TOtherKind>(&self, other: &AST<TOtherKind>) -> bool
     where
         TOtherKind: Clone + TryFrom<Any>,
@@ -251,11 +264,59 @@ where
 impl Parentable for bool {}
 impl Parentable for Slice {}
 
+pub trait Children {
+    fn collect_children(&self, children: &mut Vec<ASTAny>);
+}
+
+impl<TKind> Children for AST<TKind>
+where
+    TKind: Clone + TryFrom<Any>,
+    Any: From<TKind>,
+{
+    fn collect_children(&self, children: &mut Vec<ASTAny>) {
+        children.push(self.clone().upcast());
+    }
+}
+
+impl<T> Children for Option<T>
+where
+    T: Children,
+{
+    fn collect_children(&self, children: &mut Vec<ASTAny>) {
+        if let Some(s) = self {
+            s.collect_children(children);
+        }
+    }
+}
+
+impl<T> Children for Vec<T>
+where
+    T: Children,
+{
+    fn collect_children(&self, children: &mut Vec<ASTAny>) {
+        for ast in self.iter() {
+            ast.collect_children(children);
+        }
+    }
+}
+
+impl<T, U> Children for (T, U)
+where
+    T: Children,
+    U: Children,
+{
+    fn collect_children(&self, children: &mut Vec<ASTAny>) {
+        self.0.collect_children(children);
+        self.1.collect_children(children);
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ASTInner {
     pub parent: RefCell<Option<Weak<ASTInner>>>,
     pub slice: Slice,
     pub details: Any,
+    pub scope: OnceCell<Scope>,
 }
 
 impl PartialEq for ASTInner {
@@ -288,6 +349,10 @@ pub struct ValueDeclaration {
     pub is_const: bool,
     pub name: AST<PlainIdentifier>,
     pub declared_type: Option<AST<TypeExpression>>,
+
+    /// Declared with `:=`, so its type is fixed to the type of its initial
+    /// value
+    pub is_inferred: bool,
     pub value: Option<AST<Expression>>,
 }
 
@@ -307,11 +372,24 @@ pub struct EnumDeclaration {
 pub struct FuncDeclaration {
     pub is_static: bool,
     pub name: AST<PlainIdentifier>,
-    pub args: Vec<(AST<PlainIdentifier>, Option<AST<TypeExpression>>)>,
+    pub args: Vec<AST<FuncArg>>,
     pub return_type: Option<AST<TypeExpression>>,
     pub body: AST<Block>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncArg {
+    pub name: AST<PlainIdentifier>,
+    pub declared_type: Option<AST<TypeExpression>>,
+    pub default_value: Option<AST<Expression>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalDeclaration {
+    pub name: AST<PlainIdentifier>,
+    pub args: Vec<AST<FuncArg>>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ClassDeclaration {
     pub name: AST<PlainIdentifier>,
@@ -366,6 +444,39 @@ pub struct BinaryOperation {
     pub right: AST<Expression>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct TernaryExpression {
+    pub condition: AST<Expression>,
+    pub if_true: AST<Expression>,
+    pub if_false: AST<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCall {
+    pub function: AST<Expression>,
+    pub args: Vec<AST<Expression>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lambda {
+    pub args: Vec<AST<FuncArg>>,
+    pub return_type: Option<AST<TypeExpression>>,
+    pub body: AST<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyAccess {
+    pub subject: AST<Expression>,
+    pub property: AST<PlainIdentifier>,
+}
+
+/// Indexing into a value, as in `array[0]` or `dictionary["key"]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscript {
+    pub subject: AST<Expression>,
+    pub index: AST<Expression>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct LocalIdentifier {
     pub name: Slice,
@@ -376,6 +487,10 @@ pub struct LocalIdentifier {
 #[derive(Debug, Clone, PartialEq)]
 pub struct NullType;
 
+/// The return type of functions that don't return anything
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoidType;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct BooleanType;
 
@@ -397,18 +512,33 @@ pub struct Vector2Type;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Vector2iType;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rect2Type;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rect2iType;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Vector3Type;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Vector3iType;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vector4Type;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vector4iType;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Transform2DType;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct PlaneType;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuaternionType;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct AABBType;
 
@@ -418,6 +548,9 @@ pub struct BasisType;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Transform3DType;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectionType;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ColorType;
 
@@ -430,6 +563,45 @@ pub struct RIDType;
 #[derive(Debug, Clone, PartialEq)]
 pub struct ObjectType;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallableType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UntypedArrayType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UntypedDictionaryType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedByteArrayType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedInt32ArrayType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedInt64ArrayType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedFloat32ArrayType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedFloat64ArrayType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedStringArrayType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedVector2ArrayType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedVector3ArrayType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedColorArrayType;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct NamedType {
     pub name: AST<PlainIdentifier>,
@@ -466,7 +638,17 @@ pub struct AssignmentStatement {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct MatchStatement;
+pub struct MatchStatement {
+    pub subject: AST<Expression>,
+    pub arms: Vec<AST<MatchArm>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub patterns: Vec<AST<MatchPattern>>,
+    pub guard: Option<AST<Expression>>,
+    pub body: AST<Block>,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct WhileLoop {
@@ -483,6 +665,7 @@ pub struct IfElseStatement {
 #[derive(Debug, Clone, PartialEq)]
 pub struct ForLoop {
     pub item_name: AST<PlainIdentifier>,
+    pub item_type: Option<AST<TypeExpression>>,
     pub iteree: AST<Expression>,
     pub body: AST<Block>,
 }
@@ -493,11 +676,41 @@ pub struct Pass;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Break;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Continue;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Return {
     pub expr: Option<AST<Expression>>,
 }
 
+// --- Match patterns ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValuePattern {
+    pub value: AST<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BindingPattern {
+    pub name: AST<PlainIdentifier>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WildcardPattern;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayPattern {
+    pub members: Vec<AST<MatchPattern>>,
+    pub open_ended: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DictionaryPattern {
+    pub entries: Vec<(AST<Expression>, Option<AST<MatchPattern>>)>,
+    pub open_ended: bool,
+}
+
 // --- Misc ---
 
 #[derive(Debug, Clone, PartialEq)]
@@ -540,6 +753,7 @@ where
                 parent: RefCell::new(None),
                 slice: src,
                 details: self.into(),
+                scope: OnceCell::new(),
             }),
             PhantomData,
         )
@@ -570,6 +784,8 @@ union_type! {
         | Annotation
         | EnumDeclaration
         | FuncDeclaration
+        | FuncArg
+        | SignalDeclaration
         | ClassDeclaration
         | NullLiteral
         | BooleanLiteral
@@ -580,8 +796,14 @@ union_type! {
         | DictionaryLiteral
         | UnaryOperation
         | BinaryOperation
+        | TernaryExpression
+        | FunctionCall
+        | Lambda
+        | PropertyAccess
+        | Subscript
         | LocalIdentifier
         | NullType
+        | VoidType
         | BooleanType
         | IntType
         | FloatType
@@ -589,17 +811,36 @@ union_type! {
         | StringNameType
         | Vector2Type
         | Vector2iType
+        | Rect2Type
+        | Rect2iType
         | Vector3Type
         | Vector3iType
+        | Vector4Type
+        | Vector4iType
         | Transform2DType
         | PlaneType
+        | QuaternionType
         | AABBType
         | BasisType
         | Transform3DType
+        | ProjectionType
         | ColorType
         | NodePathType
         | RIDType
         | ObjectType
+        | CallableType
+        | SignalType
+        | UntypedArrayType
+        | UntypedDictionaryType
+        | PackedByteArrayType
+        | PackedInt32ArrayType
+        | PackedInt64ArrayType
+        | PackedFloat32ArrayType
+        | PackedFloat64ArrayType
+        | PackedStringArrayType
+        | PackedVector2ArrayType
+        | PackedVector3ArrayType
+        | PackedColorArrayType
         | NamedType
         | NonNullType
         | ArrayType
@@ -607,12 +848,19 @@ union_type! {
         | ExactDictionaryType
         | AssignmentStatement
         | MatchStatement
+        | MatchArm
         | WhileLoop
         | IfElseStatement
         | ForLoop
         | Pass
         | Break
+        | Continue
         | Return
+        | ValuePattern
+        | BindingPattern
+        | WildcardPattern
+        | ArrayPattern
+        | DictionaryPattern
         | Block
         | PlainIdentifier
         | UnaryOperator
@@ -626,6 +874,7 @@ union_subtype!(
         | Annotation
         | EnumDeclaration
         | FuncDeclaration
+        | SignalDeclaration
         | ClassDeclaration
 );
 
@@ -639,11 +888,17 @@ union_subtype!(
         | DictionaryLiteral
         | UnaryOperation
         | BinaryOperation
+        | TernaryExpression
+        | FunctionCall
+        | Lambda
+        | PropertyAccess
+        | Subscript
         | LocalIdentifier
 );
 
 union_subtype!(
     TypeExpression = NullType
+        | VoidType
         | BooleanType
         | IntType
         | FloatType
@@ -651,17 +906,36 @@ union_subtype!(
         | StringNameType
         | Vector2Type
         | Vector2iType
+        | Rect2Type
+        | Rect2iType
         | Vector3Type
         | Vector3iType
+        | Vector4Type
+        | Vector4iType
         | Transform2DType
         | PlaneType
+        | QuaternionType
         | AABBType
         | BasisType
         | Transform3DType
+        | ProjectionType
         | ColorType
         | NodePathType
         | RIDType
         | ObjectType
+        | CallableType
+        | SignalType
+        | UntypedArrayType
+        | UntypedDictionaryType
+        | PackedByteArrayType
+        | PackedInt32ArrayType
+        | PackedInt64ArrayType
+        | PackedFloat32ArrayType
+        | PackedFloat64ArrayType
+        | PackedStringArrayType
+        | PackedVector2ArrayType
+        | PackedVector3ArrayType
+        | PackedColorArrayType
         | NamedType
         | NonNullType
         | ArrayType
@@ -678,9 +952,15 @@ union_subtype!(
         | ForLoop
         | Pass
         | Break
+        | Continue
         | Return
 );
 
+union_subtype!(
+    MatchPattern =
+        ValuePattern | BindingPattern | WildcardPattern | ArrayPattern | DictionaryPattern
+);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, IntoStaticStr)]
 pub enum UnaryOperator {
     #[strum(serialize = "~")]
@@ -689,6 +969,9 @@ pub enum UnaryOperator {
     #[strum(serialize = "-")]
     Negative,
 
+    #[strum(serialize = "+")]
+    Positive,
+
     #[strum(serialize = "!")]
     Not,
 }
@@ -698,6 +981,9 @@ pub enum BinaryOperator {
     #[strum(serialize = "is")]
     Is,
 
+    #[strum(serialize = "is not")]
+    IsNot,
+
     #[strum(serialize = "**")]
     DoubleStar,
 
@@ -793,3 +1079,249 @@ impl AST<Any> {
             .map(|module| module.try_recast::<TExpected>().unwrap())
     }
 }
+
+impl<TKind> AST<TKind>
+where
+    TKind: Clone + TryFrom<Any>,
+    Any: From<TKind>,
+{
+    pub fn children(&self) -> Vec<ASTAny> {
+        let mut children = Vec::new();
+
+        match self.details() {
+            Any::GDScript(GDScript { declarations }) => {
+                declarations.collect_children(&mut children)
+            }
+            Any::ExtendsDeclaration(ExtendsDeclaration { extends_class }) => {
+                extends_class.collect_children(&mut children)
+            }
+            Any::ClassNameDeclaration(ClassNameDeclaration { class_name }) => {
+                class_name.collect_children(&mut children)
+            }
+            Any::ValueDeclaration(ValueDeclaration {
+                is_const: _,
+                name,
+                declared_type,
+                is_inferred: _,
+                value,
+            }) => {
+                name.collect_children(&mut children);
+                declared_type.collect_children(&mut children);
+                value.collect_children(&mut children);
+            }
+            Any::Annotation(Annotation { name, arguments }) => {
+                name.collect_children(&mut children);
+                arguments.collect_children(&mut children);
+            }
+            Any::EnumDeclaration(EnumDeclaration { name, variants }) => {
+                name.collect_children(&mut children);
+                variants.collect_children(&mut children);
+            }
+            Any::FuncDeclaration(FuncDeclaration {
+                is_static: _,
+                name,
+                args,
+                return_type,
+                body,
+            }) => {
+                name.collect_children(&mut children);
+                args.collect_children(&mut children);
+                return_type.collect_children(&mut children);
+                body.collect_children(&mut children);
+            }
+            Any::FuncArg(FuncArg {
+                name,
+                declared_type,
+                default_value,
+            }) => {
+                name.collect_children(&mut children);
+                declared_type.collect_children(&mut children);
+                default_value.collect_children(&mut children);
+            }
+            Any::SignalDeclaration(SignalDeclaration { name, args }) => {
+                name.collect_children(&mut children);
+                args.collect_children(&mut children);
+            }
+            Any::ClassDeclaration(ClassDeclaration { name, declarations }) => {
+                name.collect_children(&mut children);
+                declarations.collect_children(&mut children);
+            }
+            Any::ArrayLiteral(ArrayLiteral { members }) => members.collect_children(&mut children),
+            Any::DictionaryLiteral(DictionaryLiteral { entries }) => {
+                entries.collect_children(&mut children)
+            }
+            Any::UnaryOperation(UnaryOperation { op, subject }) => {
+                op.collect_children(&mut children);
+                subject.collect_children(&mut children);
+            }
+            Any::BinaryOperation(BinaryOperation { op, left, right }) => {
+                left.collect_children(&mut children);
+                op.collect_children(&mut children);
+                right.collect_children(&mut children);
+            }
+            Any::TernaryExpression(TernaryExpression {
+                condition,
+                if_true,
+                if_false,
+            }) => {
+                if_true.collect_children(&mut children);
+                condition.collect_children(&mut children);
+                if_false.collect_children(&mut children);
+            }
+            Any::FunctionCall(FunctionCall { function, args }) => {
+                function.collect_children(&mut children);
+                args.collect_children(&mut children);
+            }
+            Any::Lambda(Lambda {
+                args,
+                return_type,
+                body,
+            }) => {
+                args.collect_children(&mut children);
+                return_type.collect_children(&mut children);
+                body.collect_children(&mut children);
+            }
+            Any::PropertyAccess(PropertyAccess { subject, property }) => {
+                subject.collect_children(&mut children);
+                property.collect_children(&mut children);
+            }
+            Any::Subscript(Subscript { subject, index }) => {
+                subject.collect_children(&mut children);
+                index.collect_children(&mut children);
+            }
+            Any::NamedType(NamedType { name }) => name.collect_children(&mut children),
+            Any::NonNullType(NonNullType { inner }) => inner.collect_children(&mut children),
+            Any::ArrayType(ArrayType { element }) => element.collect_children(&mut children),
+            Any::DictionaryType(DictionaryType { key, value }) => {
+                key.collect_children(&mut children);
+                value.collect_children(&mut children);
+            }
+            Any::ExactDictionaryType(ExactDictionaryType { entries }) => {
+                entries.collect_children(&mut children)
+            }
+            Any::AssignmentStatement(AssignmentStatement {
+                target,
+                value,
+                operator,
+            }) => {
+                target.collect_children(&mut children);
+                operator.collect_children(&mut children);
+                value.collect_children(&mut children);
+            }
+            Any::WhileLoop(WhileLoop { condition, body }) => {
+                condition.collect_children(&mut children);
+                body.collect_children(&mut children);
+            }
+            Any::IfElseStatement(IfElseStatement {
+                conditions,
+                default_outcome,
+            }) => {
+                conditions.collect_children(&mut children);
+                default_outcome.collect_children(&mut children);
+            }
+            Any::ForLoop(ForLoop {
+                item_name,
+                item_type,
+                iteree,
+                body,
+            }) => {
+                item_name.collect_children(&mut children);
+                item_type.collect_children(&mut children);
+                iteree.collect_children(&mut children);
+                body.collect_children(&mut children);
+            }
+            Any::MatchStatement(MatchStatement { subject, arms }) => {
+                subject.collect_children(&mut children);
+                arms.collect_children(&mut children);
+            }
+            Any::MatchArm(MatchArm {
+                patterns,
+                guard,
+                body,
+            }) => {
+                patterns.collect_children(&mut children);
+                guard.collect_children(&mut children);
+                body.collect_children(&mut children);
+            }
+            Any::ValuePattern(ValuePattern { value }) => value.collect_children(&mut children),
+            Any::BindingPattern(BindingPattern { name }) => name.collect_children(&mut children),
+            Any::ArrayPattern(ArrayPattern {
+                members,
+                open_ended: _,
+            }) => members.collect_children(&mut children),
+            Any::DictionaryPattern(DictionaryPattern {
+                entries,
+                open_ended: _,
+            }) => entries.collect_children(&mut children),
+            Any::Return(Return { expr }) => expr.collect_children(&mut children),
+            Any::Block(Block { statements }) => statements.collect_children(&mut children),
+
+            Any::NullLiteral(_)
+            | Any::BooleanLiteral(_)
+            | Any::IntLiteral(_)
+            | Any::FloatLiteral(_)
+            | Any::StringLiteral(_)
+            | Any::LocalIdentifier(_)
+            | Any::NullType(_)
+            | Any::VoidType(_)
+            | Any::BooleanType(_)
+            | Any::IntType(_)
+            | Any::FloatType(_)
+            | Any::StringType(_)
+            | Any::StringNameType(_)
+            | Any::Vector2Type(_)
+            | Any::Vector2iType(_)
+            | Any::Rect2Type(_)
+            | Any::Rect2iType(_)
+            | Any::Vector3Type(_)
+            | Any::Vector3iType(_)
+            | Any::Vector4Type(_)
+            | Any::Vector4iType(_)
+            | Any::Transform2DType(_)
+            | Any::PlaneType(_)
+            | Any::QuaternionType(_)
+            | Any::AABBType(_)
+            | Any::BasisType(_)
+            | Any::Transform3DType(_)
+            | Any::ProjectionType(_)
+            | Any::ColorType(_)
+            | Any::NodePathType(_)
+            | Any::RIDType(_)
+            | Any::ObjectType(_)
+            | Any::CallableType(_)
+            | Any::SignalType(_)
+            | Any::UntypedArrayType(_)
+            | Any::UntypedDictionaryType(_)
+            | Any::PackedByteArrayType(_)
+            | Any::PackedInt32ArrayType(_)
+            | Any::PackedInt64ArrayType(_)
+            | Any::PackedFloat32ArrayType(_)
+            | Any::PackedFloat64ArrayType(_)
+            | Any::PackedStringArrayType(_)
+            | Any::PackedVector2ArrayType(_)
+            | Any::PackedVector3ArrayType(_)
+            | Any::PackedColorArrayType(_)
+            | Any::WildcardPattern(_)
+            | Any::Pass(_)
+            | Any::Break(_)
+            | Any::Continue(_)
+            | Any::PlainIdentifier(_)
+            | Any::UnaryOperator(_)
+            | Any::BinaryOperator(_) => {}
+        }
+
+        children
+    }
+
+    /// Every node below this one, in source order
+    pub fn descendants(&self) -> Vec<ASTAny> {
+        let mut descendants = Vec::new();
+
+        for child in self.children() {
+            descendants.push(child.clone());
+            descendants.append(&mut child.descendants());
+        }
+
+        descendants
+    }
+}
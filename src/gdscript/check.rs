@@ -1,12 +1,25 @@
+use std::num::IntErrorKind;
+
 use crate::{
     godot_project::GodotProject,
     utils::{
-        errors::GDError,
+        errors::{Fix, GDError},
         slice::{Slicable, Slice},
+        suggest::did_you_mean,
     },
 };
 
-use super::{ast::*, gd_type::Type};
+use super::{
+    assignment::AssignmentTarget,
+    ast::*,
+    const_eval::{fold_binary, fold_unary},
+    gd_type::{SubsumationContext, Type},
+    infer_type::{global_identifier_names, global_identifier_type, iteration_type, subscript_type},
+    operators::{binary_operation_type, unary_operation_type},
+    resolve_type::resolve_type_name,
+    returns::{always_returns, enclosing_function},
+    rules::{Rule, RuleSeverity},
+};
 
 #[derive(Clone, Copy, Debug)]
 pub struct CheckContext<'a> {
@@ -25,19 +38,92 @@ where
 {
     fn check<'a, F: FnMut(GDError)>(&self, ctx: CheckContext<'a>, report_error: &mut F) {
         let module_id = &ctx.module_id.clone();
-        // let subsumation_context = SubsumationContext::from(ctx);
-        let check_subsumation =
-            |destination: &Type, value: Type, slice: &Slice, report_error: &mut F| {
-                let subsumes = destination.subsumes(&value);
+        let subsumation_context = SubsumationContext::from(ctx);
+        // violations of rules the project turned off aren't reported at all
+        let report_violation =
+            |rule: Rule, slice: &Slice, message: String, fix: Option<Fix>, report_error: &mut F| {
+                let severity = ctx.godot_project.severity(rule);
 
-                if !subsumes {
-                    report_error(GDError::CheckError {
+                if severity != RuleSeverity::Off {
+                    report_error(GDError::RuleViolation {
                         module_id: module_id.clone(),
                         src: Some(slice.clone()),
-                        message: format!("Can't assign!"), // TODO
+                        rule,
+                        severity,
+                        message,
+                        fix,
                     });
                 }
             };
+        // values of type Variant are allowed anywhere, but StrictAny wants
+        // them cast or checked before they're used as something specific
+        let check_strict_any = |destination: &Type,
+                                value_type: &Type,
+                                value: &AST<Expression>,
+                                report_error: &mut F| {
+            if *value_type == Type::Any
+                && !matches!(destination, Type::Any | Type::Unknown | Type::Poisoned)
+                && ctx.godot_project.severity(Rule::StrictAny) != RuleSeverity::Off
+            {
+                let source = value.variant_source(ctx.into());
+
+                report_violation(
+                    Rule::StrictAny,
+                    source.slice(),
+                    format!(
+                        "{} is a Variant, but is used as {} without an `as` cast or `is` guard",
+                        describe_variant_source(&source),
+                        destination
+                    ),
+                    None,
+                    report_error,
+                );
+            }
+        };
+        let type_annotations = ctx.godot_project.rule_options.type_annotations;
+        let require_annotations =
+            ctx.godot_project.severity(Rule::RequireTypeAnnotations) != RuleSeverity::Off;
+        let check_subsumation = |destination: &Type,
+                                 value: Type,
+                                 slice: &Slice,
+                                 report_error: &mut F| {
+            if destination.subsumes(subsumation_context, &value) {
+                return;
+            }
+
+            // GDScript accepts a base class where a subclass is expected
+            // and checks the value at runtime, so only UnsafeCast (which
+            // is off by default) reports that
+            match destination.implicit_cast_issues(subsumation_context, &value) {
+                    Some(issues) => report_violation(
+                        Rule::AssignmentType,
+                        slice,
+                        issues.to_string(),
+                        None,
+                        report_error,
+                    ),
+                    None => report_violation(
+                        Rule::UnsafeCast,
+                        slice,
+                        format!(
+                            "Type {} is implicitly cast down to type {}, which fails at runtime if the value isn't one",
+                            value, destination
+                        ),
+                        None,
+                        report_error,
+                    ),
+                }
+        };
+
+        if let Some(conflict) = self.clone().upcast().declaration_conflict(ctx) {
+            report_violation(
+                conflict.rule,
+                conflict.name.slice(),
+                conflict.message,
+                None,
+                report_error,
+            );
+        }
 
         match self.details() {
             Any::GDScript(GDScript { declarations }) => {
@@ -45,6 +131,44 @@ where
             }
             Any::ExtendsDeclaration(ExtendsDeclaration { extends_class }) => {
                 extends_class.check(ctx, report_error);
+
+                let godot_project = ctx.godot_project;
+                let base = extends_class.downcast().name;
+
+                if !godot_project.class_exists(base.as_str()) {
+                    report_violation(
+                        Rule::InvalidInheritance,
+                        extends_class.slice(),
+                        format!("Cannot extend unknown class '{}'", base.as_str()),
+                        None,
+                        report_error,
+                    );
+                }
+
+                let class = self
+                    .clone()
+                    .upcast()
+                    .find_parent_of_type::<GDScript>()
+                    .and_then(|script| godot_project.script_class_name(&script));
+
+                if let Some(cycle) =
+                    class.and_then(|class| godot_project.inheritance_cycle(class.as_str()))
+                {
+                    report_violation(
+                        Rule::InvalidInheritance,
+                        extends_class.slice(),
+                        format!(
+                            "Cyclic inheritance: {}",
+                            cycle
+                                .iter()
+                                .map(|class| class.as_str())
+                                .collect::<Vec<_>>()
+                                .join(" -> ")
+                        ),
+                        None,
+                        report_error,
+                    );
+                }
             }
             Any::ClassNameDeclaration(ClassNameDeclaration { class_name }) => {
                 class_name.check(ctx, report_error);
@@ -53,6 +177,7 @@ where
                 is_const,
                 name,
                 declared_type,
+                is_inferred,
                 value,
             }) => {
                 name.check(ctx, report_error);
@@ -63,8 +188,62 @@ where
                     let declared_type = declared_type.resolve_type(ctx.into());
                     let value_type = value.infer_type(ctx.into());
 
+                    check_strict_any(&declared_type, &value_type, value, report_error);
                     check_subsumation(&declared_type, value_type, value.slice(), report_error);
                 }
+
+                if require_annotations && declared_type.is_none() && !is_inferred {
+                    let is_local = self
+                        .parent()
+                        .is_some_and(|parent| matches!(parent.details(), Any::Block(_)));
+                    let required = if is_local {
+                        type_annotations.variables
+                            && !(type_annotations.allow_inferred_locals && value.is_some())
+                    } else {
+                        type_annotations.variables
+                            && !(type_annotations.allow_private && is_private(name))
+                    };
+
+                    if required {
+                        report_violation(
+                            Rule::RequireTypeAnnotations,
+                            name.slice(),
+                            format!(
+                                "{} '{}' has no type annotation",
+                                if *is_const { "Constant" } else { "Variable" },
+                                name.downcast().name.as_str()
+                            ),
+                            value.as_ref().and_then(|value| {
+                                annotation_fix(name, &value.infer_type(ctx.into()))
+                            }),
+                            report_error,
+                        );
+                    }
+                }
+
+                if let (true, Some(value)) = (*is_inferred, value) {
+                    if value.infer_type(ctx.into()) == Type::Null {
+                        report_violation(
+                            Rule::InferredNull,
+                            value.slice(),
+                            format!(
+                                "Cannot infer the type of '{}' because the value is null",
+                                name.downcast().name.as_str()
+                            ),
+                            None,
+                            report_error,
+                        );
+                    }
+                }
+            }
+            Any::Lambda(Lambda {
+                args,
+                return_type,
+                body,
+            }) => {
+                args.check(ctx, report_error);
+                return_type.check(ctx, report_error);
+                body.check(ctx, report_error);
             }
             Any::Annotation(Annotation { name, arguments }) => {
                 name.check(ctx, report_error);
@@ -85,6 +264,119 @@ where
                 args.check(ctx, report_error);
                 return_type.check(ctx, report_error);
                 body.check(ctx, report_error);
+
+                let returns_value = return_type.as_ref().is_some_and(|return_type| {
+                    !matches!(
+                        return_type.resolve_type(ctx.into()),
+                        Type::Void | Type::Null | Type::Any
+                    )
+                });
+
+                if require_annotations
+                    && return_type.is_none()
+                    && type_annotations.return_types
+                    && !(type_annotations.allow_private && is_private(name))
+                {
+                    // the annotation goes right after the closing parenthesis
+                    // of the argument list
+                    let fix = self
+                        .clone()
+                        .upcast()
+                        .try_recast::<FuncDeclaration>()
+                        .and_then(|func| {
+                            let name = name.slice();
+                            let header = &name.full_string[name.end..body.slice().start];
+                            let after_args = name.end - name.start + header.rfind(')')? + 1;
+                            let annotation = func.return_type(ctx.into()).annotation()?;
+
+                            Some(Fix {
+                                src: name.clone().slice_range(after_args, Some(after_args)),
+                                replacement: format!(" -> {}", annotation),
+                            })
+                        });
+
+                    report_violation(
+                        Rule::RequireTypeAnnotations,
+                        name.slice(),
+                        format!(
+                            "Function '{}' has no return type annotation",
+                            name.downcast().name.as_str()
+                        ),
+                        fix,
+                        report_error,
+                    );
+                }
+
+                if returns_value && !always_returns(body) {
+                    report_violation(
+                        Rule::ReturnType,
+                        name.slice(),
+                        format!(
+                            "Not all code paths in '{}' return a value",
+                            name.downcast().name.as_str()
+                        ),
+                        None,
+                        report_error,
+                    );
+                }
+            }
+            Any::FuncArg(FuncArg {
+                name,
+                declared_type,
+                default_value,
+            }) => {
+                name.check(ctx, report_error);
+                declared_type.check(ctx, report_error);
+                default_value.check(ctx, report_error);
+
+                // signal arguments are only documentation, so they're left out
+                let required = self.parent().is_some_and(|parent| match parent.details() {
+                    Any::FuncDeclaration(FuncDeclaration {
+                        is_static: _,
+                        name,
+                        args: _,
+                        return_type: _,
+                        body: _,
+                    }) => !(type_annotations.allow_private && is_private(name)),
+                    Any::Lambda(_) => true,
+                    _ => false,
+                });
+
+                if require_annotations
+                    && declared_type.is_none()
+                    && type_annotations.parameters
+                    && required
+                {
+                    report_violation(
+                        Rule::RequireTypeAnnotations,
+                        name.slice(),
+                        format!(
+                            "Parameter '{}' has no type annotation",
+                            name.downcast().name.as_str()
+                        ),
+                        default_value.as_ref().and_then(|default_value| {
+                            annotation_fix(name, &default_value.infer_type(ctx.into()))
+                        }),
+                        report_error,
+                    );
+                }
+
+                if let (Some(declared_type), Some(default_value)) = (declared_type, default_value) {
+                    let declared_type = declared_type.resolve_type(ctx.into());
+                    let value_type = default_value.infer_type(ctx.into());
+
+                    check_strict_any(&declared_type, &value_type, default_value, report_error);
+                    check_subsumation(
+                        &declared_type,
+                        value_type,
+                        default_value.slice(),
+                        report_error,
+                    );
+                }
+            }
+            Any::SignalDeclaration(SignalDeclaration { name, args }) => {
+                name.check(ctx, report_error);
+                args.check(ctx, report_error);
             }
             Any::ClassDeclaration(ClassDeclaration { name, declarations }) => {
                 name.check(ctx, report_error);
@@ -97,14 +389,280 @@ where
             Any::UnaryOperation(UnaryOperation { op, subject }) => {
                 op.check(ctx, report_error);
                 subject.check(ctx, report_error);
+
+                let subject_type = subject.infer_type(ctx.into());
+
+                if let Err(error) = fold_unary(op.downcast(), &subject_type) {
+                    report_violation(
+                        error.rule(),
+                        self.slice(),
+                        error.to_string(),
+                        None,
+                        report_error,
+                    );
+                } else if unary_operation_type(op.downcast(), &subject_type, ctx.into()).is_none() {
+                    let op_name: &'static str = op.downcast().into();
+
+                    report_violation(
+                        Rule::InvalidOperation,
+                        self.slice(),
+                        format!(
+                            "Invalid operand '{}' for unary operator '{}'",
+                            subject_type.widen(),
+                            op_name
+                        ),
+                        None,
+                        report_error,
+                    );
+                }
             }
             Any::BinaryOperation(BinaryOperation { op, left, right }) => {
                 op.check(ctx, report_error);
                 left.check(ctx, report_error);
-                right.check(ctx, report_error);
+
+                match op.downcast() {
+                    BinaryOperator::As => {
+                        if right.resolve_as_type(ctx.into()).is_none() {
+                            report_violation(
+                                Rule::InvalidOperation,
+                                right.slice(),
+                                format!(
+                                    "'{}' is not a type that can be cast to",
+                                    right.slice().as_str()
+                                ),
+                                None,
+                                report_error,
+                            );
+                        }
+                    }
+                    op => {
+                        right.check(ctx, report_error);
+
+                        let left_type = left.infer_type(ctx.into());
+                        let right_type = right.infer_type(ctx.into());
+
+                        if let Err(error) = fold_binary(op, &left_type, &right_type) {
+                            report_violation(
+                                error.rule(),
+                                self.slice(),
+                                error.to_string(),
+                                None,
+                                report_error,
+                            );
+                        } else if binary_operation_type(op, &left_type, &right_type, ctx.into())
+                            .is_none()
+                        {
+                            let op_name: &'static str = op.into();
+
+                            report_violation(
+                                Rule::InvalidOperation,
+                                self.slice(),
+                                format!(
+                                    "Invalid operands '{}' and '{}' for operator '{}'",
+                                    left_type.widen(),
+                                    right_type.widen(),
+                                    op_name
+                                ),
+                                None,
+                                report_error,
+                            );
+                        }
+                    }
+                }
+            }
+            Any::TernaryExpression(TernaryExpression {
+                condition,
+                if_true,
+                if_false,
+            }) => {
+                if_true.check(ctx, report_error);
+                condition.check(ctx, report_error);
+                if_false.check(ctx, report_error);
+            }
+            Any::FunctionCall(FunctionCall { function, args }) => {
+                function.check(ctx, report_error);
+                args.check(ctx, report_error);
+
+                if let Some(callee) = self
+                    .clone()
+                    .upcast()
+                    .try_recast::<Expression>()
+                    .and_then(|call| call.callee(ctx.into()))
+                {
+                    let callee = callee.downcast();
+                    let callee_name = callee.name.downcast().name;
+                    let required = callee
+                        .args
+                        .iter()
+                        .filter(|arg| arg.downcast().default_value.is_none())
+                        .count();
+
+                    if args.len() < required || args.len() > callee.args.len() {
+                        report_violation(
+                            Rule::ArgumentCount,
+                            self.slice(),
+                            if args.len() < required {
+                                format!(
+                                    "Too few arguments for '{}()': expected at least {} but got {}",
+                                    callee_name.as_str(),
+                                    required,
+                                    args.len()
+                                )
+                            } else {
+                                format!(
+                                    "Too many arguments for '{}()': expected at most {} but got {}",
+                                    callee_name.as_str(),
+                                    callee.args.len(),
+                                    args.len()
+                                )
+                            },
+                            None,
+                            report_error,
+                        );
+                    }
+
+                    for (arg, param) in args.iter().zip(callee.args.iter()) {
+                        if let Some(declared_type) = &param.downcast().declared_type {
+                            let declared_type = declared_type.resolve_type(ctx.into());
+                            let arg_type = arg.infer_type(ctx.into());
+
+                            check_strict_any(&declared_type, &arg_type, arg, report_error);
+                            check_subsumation(&declared_type, arg_type, arg.slice(), report_error);
+                        }
+                    }
+                }
+
+                if let Expression::LocalIdentifier(LocalIdentifier { name }) = function.downcast() {
+                    let is_preload =
+                        name.as_str() == "preload" && function.resolve_symbol("preload").is_none();
+                    let path_type = args.first().map(|arg| arg.infer_type(ctx.into()));
+
+                    if is_preload && !matches!(path_type, Some(Type::String(Some(_)))) {
+                        report_violation(
+                            Rule::NonConstantPreload,
+                            self.slice(),
+                            String::from("preload() takes a constant path"),
+                            None,
+                            report_error,
+                        );
+                    }
+                }
+            }
+            Any::PropertyAccess(PropertyAccess { subject, property }) => {
+                subject.check(ctx, report_error);
+
+                // methods are looked up separately when they're called
+                let is_method_call = self.parent().is_some_and(|parent| match parent.details() {
+                    Any::FunctionCall(FunctionCall { function, args: _ }) => function.ptr_eq(self),
+                    _ => false,
+                });
+                let expression = self.clone().upcast().try_recast::<Expression>().unwrap();
+
+                // a missing member is where a poisoned type starts out, unless
+                // the subject was poisoned to begin with
+                if !is_method_call && expression.infer_type(ctx.into()) == Type::Poisoned {
+                    let property = property.downcast().name;
+                    let message = if subject.named_enum().is_some() {
+                        Some(format!(
+                            "Enum '{}' has no variant '{}'",
+                            subject.slice().as_str(),
+                            property.as_str()
+                        ))
+                    } else {
+                        match subject.resolve_as_type(ctx.into()) {
+                            Some(Type::Poisoned) => None,
+                            Some(typ) => Some(format!(
+                                "Type {} has no constant '{}'",
+                                typ,
+                                property.as_str()
+                            )),
+                            None => match subject.infer_type(ctx.into()) {
+                                Type::Poisoned => None,
+                                typ => Some(format!(
+                                    "Value of type {} has no member '{}'",
+                                    typ.widen(),
+                                    property.as_str()
+                                )),
+                            },
+                        }
+                    };
+
+                    if let Some(message) = message {
+                        report_violation(
+                            Rule::UnknownMember,
+                            self.slice(),
+                            message,
+                            None,
+                            report_error,
+                        );
+                    }
+                }
+            }
+            Any::Subscript(Subscript { subject, index }) => {
+                subject.check(ctx, report_error);
+                index.check(ctx, report_error);
+
+                let subject_type = subject.infer_type(ctx.into());
+                let index_type = index.infer_type(ctx.into());
+
+                if subscript_type(&subject_type, &index_type, ctx.into()).is_none() {
+                    report_violation(
+                        Rule::InvalidOperation,
+                        self.slice(),
+                        format!(
+                            "Cannot index into a value of type {} with a value of type {}",
+                            subject_type.widen(),
+                            index_type.widen()
+                        ),
+                        None,
+                        report_error,
+                    );
+                }
+            }
+            Any::LocalIdentifier(LocalIdentifier { name }) => {
+                let expression = self.clone().upcast().try_recast::<Expression>().unwrap();
+
+                if self.resolve_symbol(name.as_str()).is_none()
+                    && global_identifier_type(&expression, name.as_str(), ctx.into()).is_none()
+                {
+                    let mut candidates = self.names_in_scope();
+                    candidates.extend(global_identifier_names(&expression, ctx.into()));
+
+                    let suggestion =
+                        did_you_mean(name.as_str(), candidates.iter().map(String::as_str));
+
+                    report_violation(
+                        Rule::UndeclaredIdentifier,
+                        self.slice(),
+                        match suggestion {
+                            Some(suggestion) => format!(
+                                "Identifier '{}' is not declared in the current scope (did you mean '{}'?)",
+                                name.as_str(),
+                                suggestion
+                            ),
+                            None => format!(
+                                "Identifier '{}' is not declared in the current scope",
+                                name.as_str()
+                            ),
+                        },
+                        None,
+                        report_error,
+                    );
+                }
+            }
+            Any::NamedType(NamedType { name }) => {
+                let name = name.downcast().name;
+
+                if resolve_type_name(name.as_str(), ctx.into()).is_none() {
+                    report_violation(
+                        Rule::UndeclaredIdentifier,
+                        self.slice(),
+                        format!("Type '{}' is not declared", name.as_str()),
+                        None,
+                        report_error,
+                    );
+                }
             }
-            Any::LocalIdentifier(LocalIdentifier { name }) => todo!(),
-            Any::NamedType(NamedType { name }) => todo!(),
             Any::NonNullType(NonNullType { inner }) => {
                 inner.check(ctx, report_error);
             }
@@ -124,6 +682,62 @@ where
                 target.check(ctx, report_error);
                 value.check(ctx, report_error);
                 operator.check(ctx, report_error);
+
+                let destination = match target.assignment_target(ctx.into()) {
+                    AssignmentTarget::NotAssignable(message) => {
+                        report_violation(
+                            Rule::InvalidAssignment,
+                            target.slice(),
+                            message,
+                            None,
+                            report_error,
+                        );
+                        return;
+                    }
+                    AssignmentTarget::Typed(destination) => Some(destination),
+                    AssignmentTarget::Untyped => None,
+                };
+                let value_type = value.infer_type(ctx.into());
+
+                // `x += y` stores whatever `x + y` gives
+                let assigned = match operator {
+                    None => value_type,
+                    Some(operator) => {
+                        let op = operator.downcast();
+                        let target_type = target.infer_type(ctx.into());
+
+                        match binary_operation_type(op, &target_type, &value_type, ctx.into()) {
+                            Some(result) => result,
+                            None => {
+                                let op_name: &'static str = op.into();
+
+                                report_violation(
+                                    Rule::InvalidOperation,
+                                    self.slice(),
+                                    format!(
+                                        "Invalid operands '{}' and '{}' for operator '{}='",
+                                        target_type.widen(),
+                                        value_type.widen(),
+                                        op_name
+                                    ),
+                                    None,
+                                    report_error,
+                                );
+                                return;
+                            }
+                        }
+                    }
+                };
+
+                if let Some(destination) = destination {
+                    let assigned_src = match operator {
+                        None => value.slice(),
+                        Some(_) => self.slice(),
+                    };
+
+                    check_strict_any(&destination, &assigned, value, report_error);
+                    check_subsumation(&destination, assigned, assigned_src, report_error);
+                }
             }
             Any::WhileLoop(WhileLoop { condition, body }) => {
                 condition.check(ctx, report_error);
@@ -138,22 +752,161 @@ where
             }
             Any::ForLoop(ForLoop {
                 item_name,
+                item_type,
                 iteree,
                 body,
             }) => {
                 item_name.check(ctx, report_error);
+                item_type.check(ctx, report_error);
                 iteree.check(ctx, report_error);
                 body.check(ctx, report_error);
+
+                let iteree_type = iteree.infer_type(ctx.into());
+
+                match (iteration_type(&iteree_type), item_type) {
+                    (None, _) => report_violation(
+                        Rule::InvalidOperation,
+                        iteree.slice(),
+                        format!(
+                            "Cannot iterate over a value of type {}",
+                            iteree_type.widen()
+                        ),
+                        None,
+                        report_error,
+                    ),
+                    (Some(item), Some(item_type)) => {
+                        let declared_type = item_type.resolve_type(ctx.into());
+
+                        if iteree_type == Type::Any {
+                            check_strict_any(&declared_type, &item, iteree, report_error);
+                        } else if item == Type::Any
+                            && declared_type != Type::Any
+                            && ctx.godot_project.severity(Rule::StrictAny) != RuleSeverity::Off
+                        {
+                            // the collection itself is typed, just not its items
+                            report_violation(
+                                Rule::StrictAny,
+                                iteree.slice(),
+                                format!(
+                                    "The items of this {} are Variants, but are used as {} without an `is` guard",
+                                    iteree_type.widen(),
+                                    declared_type
+                                ),
+                                None,
+                                report_error,
+                            );
+                        }
+                        check_subsumation(&declared_type, item, item_type.slice(), report_error);
+                    }
+                    (Some(item), None) => {
+                        if require_annotations && type_annotations.for_variables {
+                            report_violation(
+                                Rule::RequireTypeAnnotations,
+                                item_name.slice(),
+                                format!(
+                                    "Loop variable '{}' has no type annotation",
+                                    item_name.downcast().name.as_str()
+                                ),
+                                annotation_fix(item_name, &item),
+                                report_error,
+                            );
+                        }
+                    }
+                }
+            }
+            Any::MatchStatement(MatchStatement { subject, arms }) => {
+                subject.check(ctx, report_error);
+                arms.check(ctx, report_error);
+            }
+            Any::MatchArm(MatchArm {
+                patterns,
+                guard,
+                body,
+            }) => {
+                patterns.check(ctx, report_error);
+                guard.check(ctx, report_error);
+                body.check(ctx, report_error);
+            }
+            Any::ValuePattern(ValuePattern { value }) => value.check(ctx, report_error),
+            Any::BindingPattern(BindingPattern { name }) => name.check(ctx, report_error),
+            Any::ArrayPattern(ArrayPattern {
+                members,
+                open_ended: _,
+            }) => members.check(ctx, report_error),
+            Any::DictionaryPattern(DictionaryPattern {
+                entries,
+                open_ended: _,
+            }) => entries.check(ctx, report_error),
+            Any::Return(Return { expr }) => {
+                expr.check(ctx, report_error);
+
+                let return_type = enclosing_function(&self.clone().upcast())
+                    .and_then(|function| match function.details() {
+                        Any::FuncDeclaration(FuncDeclaration {
+                            is_static: _,
+                            name: _,
+                            args: _,
+                            return_type,
+                            body: _,
+                        })
+                        | Any::Lambda(Lambda {
+                            args: _,
+                            return_type,
+                            body: _,
+                        }) => return_type.clone(),
+                        _ => None,
+                    })
+                    .map(|return_type| return_type.resolve_type(ctx.into()));
+
+                match (return_type, expr) {
+                    (Some(Type::Void), Some(expr)) => report_violation(
+                        Rule::ReturnType,
+                        expr.slice(),
+                        String::from("Cannot return a value from a void function"),
+                        None,
+                        report_error,
+                    ),
+                    (Some(Type::Void), None) | (None, _) => {}
+                    (Some(return_type), Some(expr)) => {
+                        let value_type = expr.infer_type(ctx.into());
+
+                        check_strict_any(&return_type, &value_type, expr, report_error);
+                        check_subsumation(&return_type, value_type, expr.slice(), report_error);
+                    }
+                    (Some(return_type), None) => report_violation(
+                        Rule::ReturnType,
+                        self.slice(),
+                        format!("Expected a return value of type {}", return_type),
+                        None,
+                        report_error,
+                    ),
+                }
             }
-            Any::Return(Return { expr }) => expr.check(ctx, report_error),
             Any::Block(Block { statements }) => statements.check(ctx, report_error),
 
             Any::NullLiteral(NullLiteral) => {}
             Any::BooleanLiteral(BooleanLiteral { value }) => {}
-            Any::IntLiteral(IntLiteral { value_raw }) => {}
+            Any::IntLiteral(IntLiteral { value_raw }) => {
+                if let Err(error) = value_raw.as_str().parse::<i64>() {
+                    report_violation(
+                        Rule::IntegerOverflow,
+                        self.slice(),
+                        match error.kind() {
+                            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => format!(
+                                "Integer literal {} doesn't fit in 64 bits",
+                                value_raw.as_str()
+                            ),
+                            _ => format!("Invalid integer literal {}", value_raw.as_str()),
+                        },
+                        None,
+                        report_error,
+                    );
+                }
+            }
             Any::FloatLiteral(FloatLiteral { value_raw }) => {}
             Any::StringLiteral(StringLiteral { value, multiline }) => {}
             Any::NullType(NullType) => {}
+            Any::VoidType(VoidType) => {}
             Any::BooleanType(BooleanType) => {}
             Any::IntType(IntType) => {}
             Any::FloatType(FloatType) => {}
@@ -161,20 +914,40 @@ where
             Any::StringNameType(StringNameType) => {}
             Any::Vector2Type(Vector2Type) => {}
             Any::Vector2iType(Vector2iType) => {}
+            Any::Rect2Type(Rect2Type) => {}
+            Any::Rect2iType(Rect2iType) => {}
             Any::Vector3Type(Vector3Type) => {}
             Any::Vector3iType(Vector3iType) => {}
+            Any::Vector4Type(Vector4Type) => {}
+            Any::Vector4iType(Vector4iType) => {}
             Any::Transform2DType(Transform2DType) => {}
             Any::PlaneType(PlaneType) => {}
+            Any::QuaternionType(QuaternionType) => {}
             Any::AABBType(AABBType) => {}
             Any::BasisType(BasisType) => {}
             Any::Transform3DType(Transform3DType) => {}
+            Any::ProjectionType(ProjectionType) => {}
             Any::ColorType(ColorType) => {}
             Any::NodePathType(NodePathType) => {}
             Any::RIDType(RIDType) => {}
             Any::ObjectType(ObjectType) => {}
-            Any::MatchStatement(MatchStatement) => {}
+            Any::CallableType(CallableType) => {}
+            Any::SignalType(SignalType) => {}
+            Any::UntypedArrayType(UntypedArrayType) => {}
+            Any::UntypedDictionaryType(UntypedDictionaryType) => {}
+            Any::PackedByteArrayType(PackedByteArrayType) => {}
+            Any::PackedInt32ArrayType(PackedInt32ArrayType) => {}
+            Any::PackedInt64ArrayType(PackedInt64ArrayType) => {}
+            Any::PackedFloat32ArrayType(PackedFloat32ArrayType) => {}
+            Any::PackedFloat64ArrayType(PackedFloat64ArrayType) => {}
+            Any::PackedStringArrayType(PackedStringArrayType) => {}
+            Any::PackedVector2ArrayType(PackedVector2ArrayType) => {}
+            Any::PackedVector3ArrayType(PackedVector3ArrayType) => {}
+            Any::PackedColorArrayType(PackedColorArrayType) => {}
+            Any::WildcardPattern(WildcardPattern) => {}
             Any::Pass(Pass) => {}
             Any::Break(Break) => {}
+            Any::Continue(Continue) => {}
             Any::PlainIdentifier(PlainIdentifier { name }) => {}
             Any::UnaryOperator(op) => {}
             Any::BinaryOperator(op) => {}
@@ -342,3 +1115,428 @@ where
         self.1.check(ctx, report_error);
     }
 }
+
+/// How a StrictAny diagnostic refers to the place a Variant comes from
+fn describe_variant_source(source: &ASTAny) -> String {
+    match source.details() {
+        Any::FunctionCall(FunctionCall { function, args: _ }) => {
+            format!("The result of '{}()'", function.slice().as_str())
+        }
+        Any::Subscript(_) => format!("The value of '{}'", source.slice().as_str()),
+        Any::PropertyAccess(PropertyAccess {
+            subject: _,
+            property,
+        }) => format!("Property '{}'", property.downcast().name.as_str()),
+        Any::PlainIdentifier(PlainIdentifier { name }) => {
+            format!("Untyped parameter '{}'", name.as_str())
+        }
+        Any::LocalIdentifier(LocalIdentifier { name }) => format!("'{}'", name.as_str()),
+        _ => String::from("This expression"),
+    }
+}
+
+/// Private functions and members are named with a leading underscore
+fn is_private(name: &AST<PlainIdentifier>) -> bool {
+    name.downcast().name.as_str().starts_with('_')
+}
+
+/// A fix that annotates `name` with `typ`, if GDScript has a way to write it
+fn annotation_fix(name: &AST<PlainIdentifier>, typ: &Type) -> Option<Fix> {
+    typ.annotation()
+        .filter(|annotation| annotation != "void")
+        .map(|annotation| Fix {
+            src: name.slice().clone(),
+            replacement: format!("{}: {}", name.slice().as_str(), annotation),
+        })
+}
+
+/// Parse `code` as test.gd and check it against `project`, collecting
+/// everything that's reported
+#[cfg(test)]
+pub fn check_source_in(project: &GodotProject, code: &str) -> Vec<GDError> {
+    use super::parse::parse_script;
+    use std::rc::Rc;
+
+    let module_id = ModuleID(Rc::new(String::from("test.gd")));
+    let script = parse_script(module_id.clone(), Slice::new(Rc::new(code.to_owned()))).unwrap();
+    let mut errors = Vec::new();
+
+    script.check(
+        CheckContext {
+            module_id: &module_id,
+            godot_project: project,
+        },
+        &mut |error| errors.push(error),
+    );
+
+    errors
+}
+
+/// `check_source_in()` an empty project
+#[cfg(test)]
+pub fn check_source(code: &str) -> Vec<GDError> {
+    check_source_in(&GodotProject::for_test(), code)
+}
+
+/// The source and message of each diagnostic in `errors` that points
+/// somewhere
+#[cfg(test)]
+pub fn located(errors: Vec<GDError>) -> Vec<(String, String)> {
+    errors
+        .into_iter()
+        .filter_map(|error| match error {
+            GDError::RuleViolation {
+                module_id: _,
+                src: Some(src),
+                rule: _,
+                severity: _,
+                message,
+                fix: _,
+            }
+            | GDError::CheckError {
+                module_id: _,
+                src: Some(src),
+                message,
+            } => Some((src.as_str().to_owned(), message)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn diagnostic(src: &str, message: &str) -> (String, String) {
+    (String::from(src), String::from(message))
+}
+
+#[test]
+fn undeclared_identifiers() {
+    let code = "var speed = true\nfunc f():\n\tvar a = sped\n\tvar b = speed\n\tvar c = PI\n\tvar d = later\n\tvar later = null\n\tvar e = Input\n";
+
+    assert_eq!(
+        located(check_source(code)),
+        vec![
+            diagnostic(
+                "sped",
+                "Identifier 'sped' is not declared in the current scope (did you mean 'speed'?)"
+            ),
+            diagnostic(
+                "later",
+                "Identifier 'later' is not declared in the current scope"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn type_annotations() {
+    use super::rules::{RuleOptions, TypeAnnotationOptions};
+
+    let code = "func add(a, b = true):\n\tvar total = a\n\tvar items := [true]\n\tfor i in [true, true]:\n\t\tpass\n\treturn true\nfunc _helper(x):\n\tpass\nsignal changed(value)\nvar speed = true\nconst NAME = null\nvar typed: bool = true\n";
+
+    let violations = |type_annotations: TypeAnnotationOptions| {
+        let project = GodotProject {
+            rule_severity: std::collections::HashMap::from([(
+                Rule::RequireTypeAnnotations,
+                RuleSeverity::Warning,
+            )]),
+            rule_options: RuleOptions { type_annotations },
+            ..GodotProject::for_test()
+        };
+
+        check_source_in(&project, code)
+            .into_iter()
+            .filter_map(|error| match error {
+                GDError::RuleViolation {
+                    module_id: _,
+                    src: _,
+                    rule: Rule::RequireTypeAnnotations,
+                    severity: _,
+                    message,
+                    fix,
+                } => Some((message, fix)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let all = violations(TypeAnnotationOptions::default());
+    assert_eq!(
+        all.iter()
+            .map(|(message, _)| message.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            "Parameter 'a' has no type annotation",
+            "Parameter 'b' has no type annotation",
+            "Variable 'total' has no type annotation",
+            "Loop variable 'i' has no type annotation",
+            "Function 'add' has no return type annotation",
+            "Parameter 'x' has no type annotation",
+            "Function '_helper' has no return type annotation",
+            "Variable 'speed' has no type annotation",
+            "Constant 'NAME' has no type annotation",
+        ]
+    );
+
+    // applying every fix from the end of the file backwards
+    let mut fixes: Vec<Fix> = all.into_iter().filter_map(|(_, fix)| fix).collect();
+    fixes.sort_by_key(|fix| std::cmp::Reverse(fix.src.start));
+    let mut fixed = code.to_owned();
+    for fix in fixes {
+        fixed.replace_range(fix.src.start..fix.src.end, &fix.replacement);
+    }
+    assert_eq!(
+        fixed,
+        "func add(a, b: bool = true) -> bool:\n\tvar total = a\n\tvar items := [true]\n\tfor i: bool in [true, true]:\n\t\tpass\n\treturn true\nfunc _helper(x) -> void:\n\tpass\nsignal changed(value)\nvar speed: bool = true\nconst NAME = null\nvar typed: bool = true\n"
+    );
+
+    let relaxed = violations(TypeAnnotationOptions {
+        allow_private: true,
+        allow_inferred_locals: true,
+        for_variables: false,
+        ..TypeAnnotationOptions::default()
+    });
+    assert_eq!(
+        relaxed
+            .iter()
+            .map(|(message, _)| message.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            "Parameter 'a' has no type annotation",
+            "Parameter 'b' has no type annotation",
+            "Function 'add' has no return type annotation",
+            "Variable 'speed' has no type annotation",
+            "Constant 'NAME' has no type annotation",
+        ]
+    );
+
+    // turning off variables covers locals as well as members
+    let no_variables = violations(TypeAnnotationOptions {
+        variables: false,
+        ..TypeAnnotationOptions::default()
+    });
+    assert_eq!(
+        no_variables
+            .iter()
+            .map(|(message, _)| message.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            "Parameter 'a' has no type annotation",
+            "Parameter 'b' has no type annotation",
+            "Loop variable 'i' has no type annotation",
+            "Function 'add' has no return type annotation",
+            "Parameter 'x' has no type annotation",
+            "Function '_helper' has no return type annotation",
+        ]
+    );
+}
+
+#[test]
+fn integer_overflow() {
+    let code = "var x = 99999999999999999999\nvar y: int = 9223372036854775807\nvar z = x + 1\n";
+
+    assert_eq!(
+        located(check_source(code)),
+        vec![diagnostic(
+            "99999999999999999999",
+            "Integer literal 99999999999999999999 doesn't fit in 64 bits"
+        )]
+    );
+}
+
+#[test]
+fn inferred_declarations() {
+    let code = "func f():\n\tvar speed := 10.0\n\tspeed = \"fast\"\n\tspeed = 2.0\n\tvar nothing := null\n";
+
+    assert_eq!(
+        located(check_source(code)),
+        vec![
+            diagnostic(
+                "\"fast\"",
+                "Type String (literal \"fast\") is not assignable to type float"
+            ),
+            diagnostic(
+                "null",
+                "Cannot infer the type of 'nothing' because the value is null"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn strict_any() {
+    let code = "func f(d: Dictionary, node: Node, raw, items: Array):\n\tvar a: int = d[\"key\"]\n\tvar b: String = node.get(\"name\")\n\tvar c: bool = raw\n\tvar tmp = d.get(\"x\")\n\tvar e: int = tmp\n\tfor item: int in items:\n\t\tpass\n\tvar bad = 5[0]\nfunc g() -> int:\n\treturn untyped(1)\nfunc untyped(x):\n\treturn x\n";
+
+    let diagnostics = |strict_any: RuleSeverity| {
+        let project = GodotProject {
+            rule_severity: std::collections::HashMap::from([(Rule::StrictAny, strict_any)]),
+            ..GodotProject::for_test()
+        };
+
+        located(check_source_in(&project, code))
+    };
+
+    assert_eq!(
+        diagnostics(RuleSeverity::Error),
+        vec![
+            diagnostic(
+                "d[\"key\"]",
+                "The value of 'd[\"key\"]' is a Variant, but is used as int without an `as` cast or `is` guard"
+            ),
+            diagnostic(
+                "node.get(\"name\")",
+                "The result of 'node.get()' is a Variant, but is used as String without an `as` cast or `is` guard"
+            ),
+            diagnostic(
+                "raw",
+                "Untyped parameter 'raw' is a Variant, but is used as bool without an `as` cast or `is` guard"
+            ),
+            diagnostic(
+                "d.get(\"x\")",
+                "The result of 'd.get()' is a Variant, but is used as int without an `as` cast or `is` guard"
+            ),
+            diagnostic(
+                "items",
+                "The items of this Array are Variants, but are used as int without an `is` guard"
+            ),
+            diagnostic(
+                "5[0]",
+                "Cannot index into a value of type int with a value of type int"
+            ),
+            diagnostic(
+                "untyped(1)",
+                "The result of 'untyped()' is a Variant, but is used as int without an `as` cast or `is` guard"
+            ),
+        ]
+    );
+    assert_eq!(
+        diagnostics(RuleSeverity::Off),
+        vec![diagnostic(
+            "5[0]",
+            "Cannot index into a value of type int with a value of type int"
+        )]
+    );
+}
+
+#[test]
+fn poisoned_types() {
+    let code = "func f(v: Vector2) -> int:\n\tvar a: int = typo\n\tvar b: int = typo.x\n\tvar c: int = typo.length()\n\tvar d = missing(1)\n\tvar e: int = d\n\tvar g: float = v.nope\n\tvar h: float = v.nope.x\n\tvar m: Vector2 = Vector2.ZEROO\n\tfor item: int in typo:\n\t\tpass\n\tvar k: Array[Typo] = [1]\n\tvar l: int = [typo][0]\n\treturn typo\n";
+    let project = GodotProject {
+        rule_severity: std::collections::HashMap::from([
+            (Rule::StrictAny, RuleSeverity::Error),
+            (Rule::RequireTypeAnnotations, RuleSeverity::Error),
+        ]),
+        ..GodotProject::for_test()
+    };
+
+    let undeclared = |name: &str| {
+        (
+            String::from(name),
+            format!("Identifier '{}' is not declared in the current scope", name),
+        )
+    };
+    assert_eq!(
+        located(check_source_in(&project, code)),
+        vec![
+            undeclared("typo"),
+            undeclared("typo"),
+            undeclared("typo"),
+            undeclared("missing"),
+            // the only one of these that's its own mistake
+            diagnostic("d", "Variable 'd' has no type annotation"),
+            diagnostic("v.nope", "Value of type Vector2 has no member 'nope'"),
+            diagnostic("v.nope", "Value of type Vector2 has no member 'nope'"),
+            diagnostic("Vector2.ZEROO", "Type Vector2 has no constant 'ZEROO'"),
+            undeclared("typo"),
+            diagnostic("Typo", "Type 'Typo' is not declared"),
+            undeclared("typo"),
+            undeclared("typo"),
+        ]
+    );
+}
+
+#[test]
+fn assignments() {
+    let code = "extends Node\n\nconst LIMIT = 3\nvar count: int = 0\n\nfunc f(v: Vector2) -> void:\n\tvar s: String = \"\"\n\ts = 12\n\tcount += 1\n\tcount += \"a\"\n\tcount *= 1.5\n\tv.x = 1.0\n\tLIMIT = 4\n\tmultiplayer = null\n\tname = \"node\"\n\tname = 5\n\tf(v) = 2\n\tprint = 1\n";
+
+    assert_eq!(
+        located(check_source(code)),
+        vec![
+            diagnostic(
+                "12",
+                "Type int (literal 12) is not assignable to type String"
+            ),
+            diagnostic(
+                "count += \"a\"",
+                "Invalid operands 'int' and 'String' for operator '+='"
+            ),
+            diagnostic("count *= 1.5", "Type float is not assignable to type int"),
+            diagnostic("LIMIT", "Cannot assign to constant 'LIMIT'"),
+            diagnostic("multiplayer", "Property 'multiplayer' of Node is read-only"),
+            diagnostic("5", "Type int (literal 5) is not assignable to type String"),
+            diagnostic("f(v)", "Cannot assign to this expression"),
+            diagnostic("print", "Cannot assign to 'print'"),
+        ]
+    );
+}
+
+#[test]
+fn declaration_conflicts() {
+    let project = GodotProject::for_test().with_scripts(&[(
+        "/project/enemy.gd",
+        "class_name Enemy\nextends Node\n\nvar health: int = 3\n\nfunc hit() -> void:\n\tpass\n",
+    )]);
+    let code = "extends Enemy\n\nvar speed: int = 1\nvar speed: int = 2\nvar health: int = 5\nvar name: String = \"\"\n\nfunc hit() -> void:\n\tpass\n\nfunc _ready() -> void:\n\tpass\n\nfunc f(speed: int, x: int) -> void:\n\tvar x: int = 1\n\tvar Node: int = 2\n\tvar Enemy: int = 3\n\tfor i: int in [1]:\n\t\tvar i: int = 2\n";
+
+    let rules: Vec<Rule> = check_source_in(&project, code)
+        .into_iter()
+        .filter_map(|error| match error {
+            GDError::RuleViolation {
+                module_id: _,
+                src: _,
+                rule,
+                severity: _,
+                message: _,
+                fix: _,
+            } => Some(rule),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        rules,
+        vec![
+            Rule::DuplicateDeclaration,
+            Rule::ShadowedVariableBaseClass,
+            Rule::ShadowedVariableBaseClass,
+            Rule::ShadowedVariable,
+            Rule::ShadowedVariable,
+            Rule::ShadowedGlobalIdentifier,
+            Rule::ShadowedGlobalIdentifier,
+            Rule::DuplicateDeclaration,
+        ]
+    );
+    assert_eq!(
+        located(check_source_in(&project, code)),
+        vec![
+            diagnostic("speed", "Variable 'speed' is already declared at test.gd:3:5"),
+            diagnostic(
+                "health",
+                "Variable 'health' shadows the variable inherited from Enemy, declared at /project/enemy.gd:4:5"
+            ),
+            diagnostic("name", "Variable 'name' shadows the property inherited from Node"),
+            diagnostic(
+                "speed",
+                "Parameter 'speed' shadows the variable declared at test.gd:3:5"
+            ),
+            diagnostic(
+                "x",
+                "Variable 'x' shadows the parameter declared at test.gd:14:20"
+            ),
+            diagnostic("Node", "Variable 'Node' shadows a global class"),
+            diagnostic(
+                "Enemy",
+                "Variable 'Enemy' shadows the global class declared at /project/enemy.gd:1:12"
+            ),
+            diagnostic("i", "Variable 'i' is already declared at test.gd:18:6"),
+        ]
+    );
+}
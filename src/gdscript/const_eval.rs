@@ -0,0 +1,299 @@
+use std::{fmt::Display, rc::Rc};
+
+use crate::utils::slice::Slice;
+
+use super::{
+    ast::{BinaryOperator, UnaryOperator},
+    gd_type::Type,
+    rules::Rule,
+};
+
+/// Ways evaluating a constant expression can fail at compile time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstEvalError {
+    DivisionByZero,
+    Overflow,
+}
+
+impl ConstEvalError {
+    /// The rule a failed evaluation is reported under
+    pub fn rule(self) -> Rule {
+        match self {
+            ConstEvalError::DivisionByZero => Rule::DivisionByZero,
+            ConstEvalError::Overflow => Rule::IntegerOverflow,
+        }
+    }
+}
+
+impl Display for ConstEvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstEvalError::DivisionByZero => {
+                f.write_str("Division by zero in constant expression")
+            }
+            ConstEvalError::Overflow => f.write_str("Integer overflow in constant expression"),
+        }
+    }
+}
+
+/// The literal result of `left <op> right` when both operands are literals.
+/// `Ok(None)` means the result isn't known at compile time (one of the
+/// operands isn't a literal, or the operation isn't one we evaluate); the
+/// operator table still decides whether the operation is valid at all.
+pub fn fold_binary(
+    op: BinaryOperator,
+    left: &Type,
+    right: &Type,
+) -> Result<Option<Type>, ConstEvalError> {
+    Ok(match (left, right) {
+        (Type::Int(Some(left)), Type::Int(Some(right))) => fold_int(op, *left, *right)?,
+        (Type::Int(Some(left)), Type::Float(Some(right))) => fold_float(op, *left as f64, *right),
+        (Type::Float(Some(left)), Type::Int(Some(right))) => fold_float(op, *left, *right as f64),
+        (Type::Float(Some(left)), Type::Float(Some(right))) => fold_float(op, *left, *right),
+        (Type::String(Some(left)), Type::String(Some(right))) => {
+            let (left, right) = (left.as_str(), right.as_str());
+
+            match op {
+                BinaryOperator::Plus => Some(Type::String(Some(Slice::new(Rc::new(
+                    String::from(left) + right,
+                ))))),
+                BinaryOperator::In => Some(Type::Boolean(Some(right.contains(left)))),
+                _ => compare(op, left, right),
+            }
+        }
+        (Type::Boolean(Some(left)), Type::Boolean(Some(right))) => match op {
+            BinaryOperator::Equals => Some(Type::Boolean(Some(left == right))),
+            BinaryOperator::NotEquals => Some(Type::Boolean(Some(left != right))),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// The literal result of `<op> subject` when the subject is a literal
+pub fn fold_unary(op: UnaryOperator, subject: &Type) -> Result<Option<Type>, ConstEvalError> {
+    Ok(match (op, subject) {
+        (UnaryOperator::Not, subject) => {
+            truthiness(subject).map(|value| Type::Boolean(Some(!value)))
+        }
+        (UnaryOperator::Negative, Type::Int(Some(value))) => Some(Type::Int(Some(
+            value.checked_neg().ok_or(ConstEvalError::Overflow)?,
+        ))),
+        (UnaryOperator::Negative, Type::Float(Some(value))) => Some(Type::Float(Some(-value))),
+        (UnaryOperator::Positive, typ @ (Type::Int(Some(_)) | Type::Float(Some(_)))) => {
+            Some(typ.clone())
+        }
+        (UnaryOperator::BitwiseNot, Type::Int(Some(value))) => Some(Type::Int(Some(!value))),
+        _ => None,
+    })
+}
+
+/// Whether a literal value counts as true in a condition
+pub fn truthiness(typ: &Type) -> Option<bool> {
+    match typ {
+        Type::Null => Some(false),
+        Type::Boolean(Some(value)) => Some(*value),
+        Type::Int(Some(value)) => Some(*value != 0),
+        Type::Float(Some(value)) => Some(*value != 0.0),
+        Type::String(Some(value)) => Some(!value.as_str().is_empty()),
+        _ => None,
+    }
+}
+
+/// A literal value as stored in a constant declared with type `declared`,
+/// like `const X: float = 1` holding `1.0`. `None` if the value isn't a
+/// literal of a compatible type.
+pub fn literal_as(value: &Type, declared: &Type) -> Option<Type> {
+    match (declared, value) {
+        (Type::Float(None), Type::Int(Some(value))) => Some(Type::Float(Some(*value as f64))),
+        (Type::Boolean(None), Type::Boolean(Some(_)))
+        | (Type::Int(None), Type::Int(Some(_)))
+        | (Type::Float(None), Type::Float(Some(_)))
+        | (Type::String(None), Type::String(Some(_))) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn fold_int(op: BinaryOperator, left: i64, right: i64) -> Result<Option<Type>, ConstEvalError> {
+    let int = |value: Option<i64>| {
+        value
+            .map(|value| Some(Type::Int(Some(value))))
+            .ok_or(ConstEvalError::Overflow)
+    };
+
+    match op {
+        BinaryOperator::Plus => int(left.checked_add(right)),
+        BinaryOperator::Minus => int(left.checked_sub(right)),
+        BinaryOperator::Star => int(left.checked_mul(right)),
+        BinaryOperator::Slash | BinaryOperator::Percent if right == 0 => {
+            Err(ConstEvalError::DivisionByZero)
+        }
+        BinaryOperator::Slash => int(left.checked_div(right)),
+        BinaryOperator::Percent => int(left.checked_rem(right)),
+        BinaryOperator::DoubleStar => match u32::try_from(right) {
+            Ok(exponent) => int(left.checked_pow(exponent)),
+            // negative powers aren't integers
+            Err(_) if right < 0 => Ok(None),
+            Err(_) => Err(ConstEvalError::Overflow),
+        },
+        BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight if !(0..64).contains(&right) => {
+            Ok(None)
+        }
+        BinaryOperator::ShiftLeft => Ok(Some(Type::Int(Some(left << right)))),
+        BinaryOperator::ShiftRight => Ok(Some(Type::Int(Some(left >> right)))),
+        BinaryOperator::BitwiseAnd => Ok(Some(Type::Int(Some(left & right)))),
+        BinaryOperator::BitwiseOr => Ok(Some(Type::Int(Some(left | right)))),
+        BinaryOperator::BitwiseXor => Ok(Some(Type::Int(Some(left ^ right)))),
+        op => Ok(compare(op, &left, &right)),
+    }
+}
+
+fn fold_float(op: BinaryOperator, left: f64, right: f64) -> Option<Type> {
+    let result = match op {
+        BinaryOperator::Plus => left + right,
+        BinaryOperator::Minus => left - right,
+        BinaryOperator::Star => left * right,
+        // float division by zero is well-defined (inf or nan)
+        BinaryOperator::Slash => left / right,
+        BinaryOperator::DoubleStar => left.powf(right),
+        op => return compare(op, &left, &right),
+    };
+
+    // nan isn't equal to itself, so it can't be a literal type
+    Some(Type::Float(Some(result).filter(|result| !result.is_nan())))
+}
+
+fn compare<T: PartialOrd + ?Sized>(op: BinaryOperator, left: &T, right: &T) -> Option<Type> {
+    let result = match op {
+        BinaryOperator::Equals => left == right,
+        BinaryOperator::NotEquals => left != right,
+        BinaryOperator::Less => left < right,
+        BinaryOperator::Greater => left > right,
+        BinaryOperator::LessEqual => left <= right,
+        BinaryOperator::GreaterEqual => left >= right,
+        _ => return None,
+    };
+
+    Some(Type::Boolean(Some(result)))
+}
+
+#[test]
+fn constant_folding() {
+    let string = |s: &str| Type::String(Some(Slice::new(Rc::new(s.to_owned()))));
+
+    assert_eq!(
+        fold_binary(
+            BinaryOperator::Star,
+            &Type::Int(Some(6)),
+            &Type::Int(Some(7))
+        ),
+        Ok(Some(Type::Int(Some(42))))
+    );
+    assert_eq!(
+        fold_binary(
+            BinaryOperator::Slash,
+            &Type::Int(Some(7)),
+            &Type::Int(Some(2))
+        ),
+        Ok(Some(Type::Int(Some(3))))
+    );
+    assert_eq!(
+        fold_binary(
+            BinaryOperator::Plus,
+            &Type::Int(Some(1)),
+            &Type::Float(Some(0.5))
+        ),
+        Ok(Some(Type::Float(Some(1.5))))
+    );
+    assert_eq!(
+        fold_binary(
+            BinaryOperator::Slash,
+            &Type::Float(Some(0.0)),
+            &Type::Float(Some(0.0))
+        ),
+        Ok(Some(Type::Float(None)))
+    );
+    assert_eq!(
+        fold_binary(
+            BinaryOperator::Slash,
+            &Type::Float(Some(1.0)),
+            &Type::Float(Some(0.0))
+        ),
+        Ok(Some(Type::Float(Some(f64::INFINITY))))
+    );
+    assert_eq!(
+        fold_binary(BinaryOperator::Plus, &string("foo"), &string("bar")),
+        Ok(Some(string("foobar")))
+    );
+    assert_eq!(
+        fold_binary(
+            BinaryOperator::Less,
+            &Type::Int(Some(1)),
+            &Type::Int(Some(2))
+        ),
+        Ok(Some(Type::Boolean(Some(true))))
+    );
+    assert_eq!(
+        fold_binary(BinaryOperator::Plus, &Type::Int(Some(1)), &Type::Int(None)),
+        Ok(None)
+    );
+
+    assert_eq!(
+        fold_binary(
+            BinaryOperator::Percent,
+            &Type::Int(Some(1)),
+            &Type::Int(Some(0))
+        ),
+        Err(ConstEvalError::DivisionByZero)
+    );
+    assert_eq!(
+        fold_binary(
+            BinaryOperator::Plus,
+            &Type::Int(Some(i64::MAX)),
+            &Type::Int(Some(1))
+        ),
+        Err(ConstEvalError::Overflow)
+    );
+    assert_eq!(
+        fold_unary(UnaryOperator::Negative, &Type::Int(Some(i64::MIN))),
+        Err(ConstEvalError::Overflow)
+    );
+    assert_eq!(
+        fold_unary(UnaryOperator::Not, &string("")),
+        Ok(Some(Type::Boolean(Some(true))))
+    );
+
+    assert_eq!(
+        literal_as(&Type::Int(Some(1)), &Type::Float(None)),
+        Some(Type::Float(Some(1.0)))
+    );
+}
+
+#[test]
+fn parsed_constant_folding() {
+    use super::check::{check_source, located};
+
+    let code = "var a: bool = 2 * 3 + 1\nvar b: bool = -(1 - 4) ** 2\nvar c: bool = 0.0 / 0.0\nvar d: bool = 1 << 2 == 4\nvar e: int = 1 / 0\n";
+
+    assert_eq!(
+        located(check_source(code)),
+        vec![
+            (
+                String::from("2 * 3 + 1"),
+                String::from("Type int (literal 7) is not assignable to type bool")
+            ),
+            (
+                String::from("-(1 - 4) ** 2"),
+                String::from("Type int (literal -9) is not assignable to type bool")
+            ),
+            (
+                String::from("0.0 / 0.0"),
+                String::from("Type float is not assignable to type bool")
+            ),
+            (
+                String::from("1 / 0"),
+                String::from("Division by zero in constant expression")
+            ),
+        ]
+    );
+}
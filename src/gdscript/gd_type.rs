@@ -1,40 +1,1022 @@
-use std::rc::Rc;
+use std::{
+    fmt::{Display, Write},
+    rc::Rc,
+};
 
-use crate::utils::slice::Slice;
+use crate::{godot_project::GodotProject, utils::slice::Slice};
+
+use super::{check::CheckContext, infer_type::InferTypeContext};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Null,
+
+    /// What calling a function that doesn't return anything gives, which
+    /// (unlike `null`) can't be used as a value
+    Void,
     Boolean(Option<bool>),
     Int(Option<i64>),
     Float(Option<f64>),
     String(Option<Slice>),
+    StringName,
     Vector2,
     Vector2i,
+    Rect2,
+    Rect2i,
     Vector3,
     Vector3i,
     Transform2D,
+    Vector4,
+    Vector4i,
     Plane,
+    Quaternion,
     AABB,
     Basis,
     Transform3D,
+    Projection,
     Color,
     NodePath,
     RID,
     Object,
-    NonNull { inner: Rc<Type> },
-    Array { element: Rc<Type> },
-    Dictionary { key: Rc<Type>, value: Rc<Type> },
-    ExactDictionary { entries: Vec<(Type, Type)> },
-    ExactArray { members: Vec<Type> },
+    Callable,
+    Signal,
+    PackedByteArray,
+    PackedInt32Array,
+    PackedInt64Array,
+    PackedFloat32Array,
+    PackedFloat64Array,
+    PackedStringArray,
+    PackedVector2Array,
+    PackedVector3Array,
+    PackedColorArray,
+    Class {
+        name: Rc<String>,
+    },
+    NonNull {
+        inner: Rc<Type>,
+    },
+    Array {
+        element: Rc<Type>,
+    },
+    Dictionary {
+        key: Rc<Type>,
+        value: Rc<Type>,
+    },
+    ExactDictionary {
+        entries: Vec<(Type, Type)>,
+    },
+    ExactArray {
+        members: Vec<Type>,
+    },
+    Union {
+        members: Vec<Type>,
+    },
 
+    /// A type that can't be known without going in circles, like that of
+    /// a variable initialized from itself. Nothing has been reported about
+    /// it, and it's accepted wherever a type is expected.
     Unknown,
+
+    /// The type of an expression that has already been reported as an
+    /// error. Every check involving a poisoned type passes silently, and
+    /// anything computed from one is poisoned too, so a single mistake
+    /// produces a single diagnostic.
     Poisoned,
     Any,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct SubsumationContext<'a> {
+    pub godot_project: &'a GodotProject,
+}
+
+impl<'a> From<CheckContext<'a>> for SubsumationContext<'a> {
+    fn from(ctx: CheckContext<'a>) -> Self {
+        Self {
+            godot_project: ctx.godot_project,
+        }
+    }
+}
+
+impl<'a> From<InferTypeContext<'a>> for SubsumationContext<'a> {
+    fn from(ctx: InferTypeContext<'a>) -> Self {
+        Self {
+            godot_project: ctx.godot_project,
+        }
+    }
+}
+
+/// Describes why a value type couldn't be assigned to a destination type.
+/// Each level is a `(destination, value)` pair, starting with the outermost
+/// types and ending with the innermost pair that actually conflicted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubsumationIssue {
+    Assignment(Vec<(Type, Type)>),
+}
+
+impl Type {
+    /// Whether a value of type `other` can be assigned to a location of
+    /// type `self`
+    pub fn subsumes<'a>(&self, ctx: SubsumationContext<'a>, other: &Type) -> bool {
+        self.subsumation_issues(ctx, other).is_none()
+    }
+
+    pub fn subsumation_issues<'a>(
+        &self,
+        ctx: SubsumationContext<'a>,
+        other: &Type,
+    ) -> Option<SubsumationIssue> {
+        self.assignment_issues(ctx, other, false)
+    }
+
+    /// Like `subsumation_issues`, but also accepting a value of a base
+    /// class where one of its subclasses is expected. GDScript allows that
+    /// implicit downcast and checks it at runtime.
+    pub fn implicit_cast_issues<'a>(
+        &self,
+        ctx: SubsumationContext<'a>,
+        other: &Type,
+    ) -> Option<SubsumationIssue> {
+        self.assignment_issues(ctx, other, true)
+    }
+
+    fn assignment_issues<'a>(
+        &self,
+        ctx: SubsumationContext<'a>,
+        other: &Type,
+        downcasts: bool,
+    ) -> Option<SubsumationIssue> {
+        let mismatch = || {
+            Some(SubsumationIssue::Assignment(vec![(
+                self.clone(),
+                other.clone(),
+            )]))
+        };
+
+        // nested issues get our own level prepended, so the final list reads
+        // from the outermost types down to the one that actually conflicted
+        let nested = |issue: SubsumationIssue| {
+            let SubsumationIssue::Assignment(levels) = issue;
+
+            Some(SubsumationIssue::Assignment(
+                std::iter::once((self.clone(), other.clone()))
+                    .chain(levels)
+                    .collect(),
+            ))
+        };
+
+        match (self, other) {
+            // dynamically-typed values go anywhere, and anywhere accepts them
+            (Type::Any, _) | (_, Type::Any) => None,
+            (Type::Unknown, _) | (_, Type::Unknown) => None,
+            (Type::Poisoned, _) | (_, Type::Poisoned) => None,
+
+            // every possible value has to fit...
+            (destination, Type::Union { members }) => members
+                .iter()
+                .find_map(|member| destination.assignment_issues(ctx, member, downcasts))
+                .and_then(nested),
+            // ...but it only has to fit one of the possible destinations
+            (Type::Union { members }, value) => {
+                if members
+                    .iter()
+                    .any(|member| member.assignment_issues(ctx, value, downcasts).is_none())
+                {
+                    None
+                } else {
+                    mismatch()
+                }
+            }
+
+            (Type::NonNull { inner: _ }, Type::Null) => mismatch(),
+            (Type::NonNull { inner }, Type::NonNull { inner: other_inner }) => inner
+                .assignment_issues(ctx, other_inner, downcasts)
+                .and_then(nested),
+            (Type::NonNull { inner }, other) => inner
+                .assignment_issues(ctx, other, downcasts)
+                .and_then(nested),
+            (destination, Type::NonNull { inner }) => destination
+                .assignment_issues(ctx, inner, downcasts)
+                .and_then(nested),
+
+            (Type::Null, Type::Null) => None,
+            (Type::Object, Type::Null) => None,
+            (Type::Object, Type::Object) => None,
+            (Type::Object, Type::Class { name: _ }) => None,
+            (Type::Class { name: _ }, Type::Null) => None,
+            (Type::Class { name: _ }, Type::Object) if downcasts => None,
+            (Type::Class { name }, Type::Class { name: other_name }) => {
+                if ctx.godot_project.inherits(other_name, name)
+                    || (downcasts && ctx.godot_project.inherits(name, other_name))
+                {
+                    None
+                } else {
+                    mismatch()
+                }
+            }
+
+            // literal types widen to their base type
+            (Type::Boolean(None), Type::Boolean(_)) => None,
+            (Type::Int(None), Type::Int(_)) => None,
+            (Type::Float(None), Type::Float(_)) => None,
+            (Type::String(None), Type::String(_)) => None,
+
+            // ints are implicitly promoted to floats
+            (Type::Float(None), Type::Int(_)) => None,
+            (Type::Float(Some(expected)), Type::Int(Some(value))) if *expected == *value as f64 => {
+                None
+            }
+
+            // Strings and StringNames implicitly convert to each other
+            (Type::StringName, Type::String(_)) => None,
+            (Type::String(None), Type::StringName) => None,
+
+            // array literals implicitly convert to packed arrays
+            (packed, Type::ExactArray { members }) if packed.packed_element().is_some() => {
+                let element = packed.packed_element().unwrap();
+
+                members
+                    .iter()
+                    .find_map(|member| element.assignment_issues(ctx, member, downcasts))
+                    .and_then(nested)
+            }
+
+            (
+                Type::Array { element },
+                Type::Array {
+                    element: other_element,
+                },
+            ) => {
+                // typed arrays are invariant in Godot, so only an untyped
+                // array on either side allows the element types to differ
+                if element.as_ref() == &Type::Any
+                    || other_element.as_ref() == &Type::Any
+                    || element.equivalent_to(ctx, other_element)
+                {
+                    None
+                } else {
+                    mismatch()
+                }
+            }
+            (Type::Array { element }, Type::ExactArray { members }) => members
+                .iter()
+                .find_map(|member| element.assignment_issues(ctx, member, downcasts))
+                .and_then(nested),
+            (
+                Type::ExactArray { members },
+                Type::ExactArray {
+                    members: other_members,
+                },
+            ) => {
+                if members.len() != other_members.len() {
+                    mismatch()
+                } else {
+                    members
+                        .iter()
+                        .zip(other_members.iter())
+                        .find_map(|(member, other_member)| {
+                            member.assignment_issues(ctx, other_member, downcasts)
+                        })
+                        .and_then(nested)
+                }
+            }
+
+            (
+                Type::Dictionary { key, value },
+                Type::Dictionary {
+                    key: other_key,
+                    value: other_value,
+                },
+            ) => {
+                let compatible = |destination: &Type, value: &Type| {
+                    destination == &Type::Any
+                        || value == &Type::Any
+                        || destination.equivalent_to(ctx, value)
+                };
+
+                if compatible(key, other_key) && compatible(value, other_value) {
+                    None
+                } else {
+                    mismatch()
+                }
+            }
+            (Type::Dictionary { key, value }, Type::ExactDictionary { entries }) => entries
+                .iter()
+                .find_map(|(entry_key, entry_value)| {
+                    key.assignment_issues(ctx, entry_key, downcasts)
+                        .or_else(|| value.assignment_issues(ctx, entry_value, downcasts))
+                })
+                .and_then(nested),
+            (
+                Type::ExactDictionary { entries },
+                Type::ExactDictionary {
+                    entries: other_entries,
+                },
+            ) => {
+                let mut matched_entries = Vec::with_capacity(entries.len());
+
+                for (key, value) in entries {
+                    match other_entries.iter().find(|(other_key, _)| other_key == key) {
+                        Some((_, other_value)) => matched_entries.push((value, other_value)),
+                        None => return mismatch(),
+                    }
+                }
+
+                matched_entries
+                    .into_iter()
+                    .find_map(|(value, other_value)| {
+                        value.assignment_issues(ctx, other_value, downcasts)
+                    })
+                    .and_then(nested)
+            }
+
+            (destination, value) => {
+                if destination == value {
+                    None
+                } else {
+                    mismatch()
+                }
+            }
+        }
+    }
+
+    /// Build a normalized union out of some types: nested unions are
+    /// flattened, duplicates (including literals whose base type is already
+    /// present) are removed, and `Poisoned` or else `Any` absorbs everything
+    /// else. `Unknown` members are dropped as long as something else is
+    /// known.
+    pub fn union<I: IntoIterator<Item = Type>>(types: I) -> Type {
+        let mut flattened = Vec::new();
+
+        for typ in types {
+            match typ {
+                Type::Union { members } => flattened.extend(members),
+                other => flattened.push(other),
+            }
+        }
+
+        if flattened.contains(&Type::Poisoned) {
+            return Type::Poisoned;
+        }
+
+        if flattened.contains(&Type::Any) {
+            return Type::Any;
+        }
+
+        if flattened.iter().any(|typ| typ != &Type::Unknown) {
+            flattened.retain(|typ| typ != &Type::Unknown);
+        }
+
+        let mut members: Vec<Type> = Vec::with_capacity(flattened.len());
+        for typ in flattened {
+            let redundant = members.contains(&typ)
+                || typ
+                    .widen_literal()
+                    .is_some_and(|base| members.contains(&base));
+
+            if !redundant {
+                // a base type makes any literals of itself redundant, so it
+                // takes the place of the first one
+                let first_literal = members
+                    .iter()
+                    .position(|member| member.widen_literal().as_ref() == Some(&typ));
+
+                match first_literal {
+                    Some(index) => {
+                        members.retain(|member| member.widen_literal().as_ref() != Some(&typ));
+                        members.insert(index, typ);
+                    }
+                    None => members.push(typ),
+                }
+            }
+        }
+
+        match members.len() {
+            0 => Type::Unknown,
+            1 => members.remove(0),
+            _ => Type::Union { members },
+        }
+    }
+
+    /// If this is a literal type, its base type
+    fn widen_literal(&self) -> Option<Type> {
+        match self {
+            Type::Boolean(Some(_)) => Some(Type::Boolean(None)),
+            Type::Int(Some(_)) => Some(Type::Int(None)),
+            Type::Float(Some(_)) => Some(Type::Float(None)),
+            Type::String(Some(_)) => Some(Type::String(None)),
+            _ => None,
+        }
+    }
+
+    /// The type a mutable location holding a value of this type should
+    /// have: literals become their base type, and literal collections
+    /// become untyped collections because their contents may change.
+    pub fn widen(&self) -> Type {
+        match self {
+            Type::ExactArray { members: _ } => Type::Array {
+                element: Rc::new(Type::Any),
+            },
+            Type::ExactDictionary { entries: _ } => Type::Dictionary {
+                key: Rc::new(Type::Any),
+                value: Rc::new(Type::Any),
+            },
+            Type::Union { members } => Type::union(members.iter().map(Type::widen)),
+            other => other.widen_literal().unwrap_or_else(|| other.clone()),
+        }
+    }
+
+    /// Narrow this type down to the part of it that is also a `target`, as
+    /// after a successful `is` check
+    pub fn narrow_to<'a>(&self, ctx: SubsumationContext<'a>, target: &Type) -> Type {
+        let target_includes_null = match target {
+            Type::Null => true,
+            Type::Union { members } => members.contains(&Type::Null),
+            _ => false,
+        };
+
+        let narrow_member = |member: &Type| {
+            if *member == Type::Null {
+                // null passes a check for a class type when assigning, but
+                // not when checking with `is`
+                if target_includes_null {
+                    Some(Type::Null)
+                } else {
+                    None
+                }
+            } else if target.subsumes(ctx, member) {
+                Some(member.clone())
+            } else if member.subsumes(ctx, target) {
+                Some(target.clone())
+            } else {
+                None
+            }
+        };
+
+        let narrowed = match self {
+            Type::Any | Type::Unknown => target.clone(),
+            Type::Poisoned => return Type::Poisoned,
+            Type::Union { members } => {
+                let kept: Vec<Type> = members.iter().filter_map(narrow_member).collect();
+
+                if kept.is_empty() {
+                    target.clone()
+                } else {
+                    Type::union(kept)
+                }
+            }
+            other => narrow_member(other).unwrap_or_else(|| target.clone()),
+        };
+
+        if target_includes_null {
+            narrowed
+        } else {
+            narrowed.non_null()
+        }
+    }
+
+    /// Remove the parts of this type that are a `target`, as after a failed
+    /// `is` check
+    pub fn narrow_away<'a>(&self, ctx: SubsumationContext<'a>, target: &Type) -> Type {
+        match self {
+            Type::Union { members } => {
+                let kept: Vec<Type> = members
+                    .iter()
+                    .filter(|member| !target.subsumes(ctx, member))
+                    .cloned()
+                    .collect();
+
+                if kept.is_empty() {
+                    self.clone()
+                } else {
+                    Type::union(kept)
+                }
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// This type, given that the value is known not to be `null`
+    pub fn non_null(&self) -> Type {
+        match self {
+            Type::Union { members } => {
+                let kept: Vec<Type> = members
+                    .iter()
+                    .filter(|member| **member != Type::Null)
+                    .map(Type::non_null)
+                    .collect();
+
+                if kept.is_empty() {
+                    self.clone()
+                } else {
+                    Type::union(kept)
+                }
+            }
+            Type::Object | Type::Class { name: _ } => Type::NonNull {
+                inner: Rc::new(self.clone()),
+            },
+            other => other.clone(),
+        }
+    }
+
+    /// Whether each type subsumes the other
+    pub fn equivalent_to<'a>(&self, ctx: SubsumationContext<'a>, other: &Type) -> bool {
+        self.subsumes(ctx, other) && other.subsumes(ctx, self)
+    }
+
+    /// How this type is written as an annotation in GDScript, or `None` if
+    /// GDScript has no way to spell it (like most unions, or scripts
+    /// without a `class_name`)
+    pub fn annotation(&self) -> Option<String> {
+        match self.widen() {
+            Type::Void => Some(String::from("void")),
+            Type::Null | Type::Unknown | Type::Poisoned | Type::Any => None,
+            Type::Class { name } if name.starts_with("res://") => None,
+            Type::Class { name } => Some(name.to_string()),
+            Type::NonNull { inner } => inner.annotation(),
+            // class types are nullable anyway
+            Type::Union { members } => match members.as_slice() {
+                [Type::Null, class @ (Type::Object | Type::Class { name: _ })]
+                | [class @ (Type::Object | Type::Class { name: _ }), Type::Null] => {
+                    class.annotation()
+                }
+                _ => None,
+            },
+            // GDScript doesn't have nested typed collections
+            Type::Array { element } => match element.as_ref() {
+                Type::Array { element: _ } | Type::Dictionary { key: _, value: _ } => {
+                    Some(String::from("Array"))
+                }
+                element => Some(
+                    element
+                        .annotation()
+                        .filter(|element| element != "void")
+                        .map(|element| format!("Array[{}]", element))
+                        .unwrap_or_else(|| String::from("Array")),
+                ),
+            },
+            Type::Dictionary { key: _, value: _ } => Some(String::from("Dictionary")),
+            other => other.api_name().map(String::from),
+        }
+    }
+
+    /// The name the engine API uses for this type, for looking up its
+    /// members, methods and operators
+    pub fn api_name(&self) -> Option<&str> {
+        match self {
+            Type::Null => Some("Nil"),
+            Type::Boolean(_) => Some("bool"),
+            Type::Int(_) => Some("int"),
+            Type::Float(_) => Some("float"),
+            Type::String(_) => Some("String"),
+            Type::StringName => Some("StringName"),
+            Type::Vector2 => Some("Vector2"),
+            Type::Vector2i => Some("Vector2i"),
+            Type::Rect2 => Some("Rect2"),
+            Type::Rect2i => Some("Rect2i"),
+            Type::Vector3 => Some("Vector3"),
+            Type::Vector3i => Some("Vector3i"),
+            Type::Transform2D => Some("Transform2D"),
+            Type::Vector4 => Some("Vector4"),
+            Type::Vector4i => Some("Vector4i"),
+            Type::Plane => Some("Plane"),
+            Type::Quaternion => Some("Quaternion"),
+            Type::AABB => Some("AABB"),
+            Type::Basis => Some("Basis"),
+            Type::Transform3D => Some("Transform3D"),
+            Type::Projection => Some("Projection"),
+            Type::Color => Some("Color"),
+            Type::NodePath => Some("NodePath"),
+            Type::RID => Some("RID"),
+            Type::Object => Some("Object"),
+            Type::Callable => Some("Callable"),
+            Type::Signal => Some("Signal"),
+            Type::PackedByteArray => Some("PackedByteArray"),
+            Type::PackedInt32Array => Some("PackedInt32Array"),
+            Type::PackedInt64Array => Some("PackedInt64Array"),
+            Type::PackedFloat32Array => Some("PackedFloat32Array"),
+            Type::PackedFloat64Array => Some("PackedFloat64Array"),
+            Type::PackedStringArray => Some("PackedStringArray"),
+            Type::PackedVector2Array => Some("PackedVector2Array"),
+            Type::PackedVector3Array => Some("PackedVector3Array"),
+            Type::PackedColorArray => Some("PackedColorArray"),
+            Type::Class { name } => Some(name.as_str()),
+            Type::NonNull { inner } => inner.api_name(),
+            Type::Array { element: _ } | Type::ExactArray { members: _ } => Some("Array"),
+            Type::Dictionary { key: _, value: _ } | Type::ExactDictionary { entries: _ } => {
+                Some("Dictionary")
+            }
+            Type::Void
+            | Type::Union { members: _ }
+            | Type::Unknown
+            | Type::Poisoned
+            | Type::Any => None,
+        }
+    }
+
+    /// The element type, if this is one of the packed array types
+    pub fn packed_element(&self) -> Option<Type> {
+        match self {
+            Type::PackedByteArray | Type::PackedInt32Array | Type::PackedInt64Array => {
+                Some(Type::Int(None))
+            }
+            Type::PackedFloat32Array | Type::PackedFloat64Array => Some(Type::Float(None)),
+            Type::PackedStringArray => Some(Type::String(None)),
+            Type::PackedVector2Array => Some(Type::Vector2),
+            Type::PackedVector3Array => Some(Type::Vector3),
+            Type::PackedColorArray => Some(Type::Color),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Null => f.write_str("null"),
+            Type::Void => f.write_str("void"),
+            Type::Boolean(None) => f.write_str("bool"),
+            Type::Boolean(Some(value)) => write!(f, "bool (literal {})", value),
+            Type::Int(None) => f.write_str("int"),
+            Type::Int(Some(value)) => write!(f, "int (literal {})", value),
+            Type::Float(None) => f.write_str("float"),
+            Type::Float(Some(value)) => write!(f, "float (literal {:?})", value),
+            Type::String(None) => f.write_str("String"),
+            Type::String(Some(value)) => write!(f, "String (literal \"{}\")", value.as_str()),
+            Type::Class { name } => f.write_str(name),
+            Type::NonNull { inner } => match inner.as_ref() {
+                Type::Union { members: _ } => write!(f, "({})!", inner),
+                inner => write!(f, "{}!", inner),
+            },
+            Type::Array { element } => match element.as_ref() {
+                Type::Any => f.write_str("Array"),
+                element => write!(f, "Array[{}]", element),
+            },
+            Type::Dictionary { key, value } => match (key.as_ref(), value.as_ref()) {
+                (Type::Any, Type::Any) => f.write_str("Dictionary"),
+                (key, value) => write!(f, "Dictionary[{}, {}]", key, value),
+            },
+            Type::ExactDictionary { entries } => {
+                if entries.is_empty() {
+                    return f.write_str("{}");
+                }
+
+                f.write_str("{ ")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+
+                    key.fmt_as_key(f)?;
+                    write!(f, ": {}", value)?;
+                }
+                f.write_str(" }")
+            }
+            Type::ExactArray { members } => {
+                f.write_char('[')?;
+                for (index, member) in members.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+
+                    write!(f, "{}", member)?;
+                }
+                f.write_char(']')
+            }
+            Type::Union { members } => {
+                for (index, member) in members.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(" | ")?;
+                    }
+
+                    write!(f, "{}", member)?;
+                }
+
+                Ok(())
+            }
+            Type::Unknown => f.write_str("unknown"),
+            Type::Poisoned => f.write_str("<error>"),
+            Type::Any => f.write_str("Variant"),
+            other => f.write_str(other.api_name().unwrap()),
+        }
+    }
+}
+
 impl Type {
-    pub fn subsumes(&self, other: &Type) -> bool {
-        self == other
+    /// Dictionary keys are usually literals, so we print their value
+    /// instead of their type where we can
+    fn fmt_as_key(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Boolean(Some(value)) => write!(f, "{}", value),
+            Type::Int(Some(value)) => write!(f, "{}", value),
+            Type::Float(Some(value)) => write!(f, "{:?}", value),
+            Type::String(Some(value)) => write!(f, "\"{}\"", value.as_str()),
+            Type::Null => f.write_str("null"),
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+impl Display for SubsumationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubsumationIssue::Assignment(levels) => {
+                for (index, (destination, value)) in levels.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char('\n')?;
+                    }
+
+                    // each level explains the one above it
+                    for _ in 0..index {
+                        f.write_str("  ")?;
+                    }
+
+                    write!(
+                        f,
+                        "Type {} is not assignable to type {}",
+                        value, destination
+                    )?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[test]
+fn subsumes_literals_and_promotion() {
+    let project = GodotProject::for_test();
+    let ctx = SubsumationContext {
+        godot_project: &project,
+    };
+
+    assert!(Type::Int(None).subsumes(ctx, &Type::Int(Some(5))));
+    assert!(Type::Float(None).subsumes(ctx, &Type::Int(Some(1))));
+    assert!(Type::Float(None).subsumes(ctx, &Type::Float(Some(1.5))));
+    assert!(!Type::Int(None).subsumes(ctx, &Type::Float(None)));
+    assert!(!Type::Int(Some(5)).subsumes(ctx, &Type::Int(Some(6))));
+    assert!(!Type::Int(Some(5)).subsumes(ctx, &Type::Int(None)));
+
+    assert!(Type::Int(None).subsumes(ctx, &Type::Any));
+    assert!(Type::Any.subsumes(ctx, &Type::Vector2));
+    assert!(Type::Color.subsumes(ctx, &Type::Poisoned));
+}
+
+#[test]
+fn subsumes_null_and_collections() {
+    let project = GodotProject::for_test();
+    let ctx = SubsumationContext {
+        godot_project: &project,
+    };
+
+    assert!(Type::Object.subsumes(ctx, &Type::Null));
+    assert!(!Type::NonNull {
+        inner: Rc::new(Type::Object)
     }
+    .subsumes(ctx, &Type::Null));
+    assert!(!Type::Int(None).subsumes(ctx, &Type::Null));
+
+    let int_array = Type::Array {
+        element: Rc::new(Type::Int(None)),
+    };
+    let float_array = Type::Array {
+        element: Rc::new(Type::Float(None)),
+    };
+    assert!(int_array.subsumes(
+        ctx,
+        &Type::ExactArray {
+            members: vec![Type::Int(Some(1)), Type::Int(Some(2))]
+        }
+    ));
+    assert!(!float_array.subsumes(ctx, &int_array));
+
+    let literal = Type::ExactArray {
+        members: vec![Type::Int(Some(1)), Type::Boolean(Some(true))],
+    };
+    assert_eq!(
+        int_array.subsumation_issues(ctx, &literal),
+        Some(SubsumationIssue::Assignment(vec![
+            (int_array.clone(), literal.clone()),
+            (Type::Int(None), Type::Boolean(Some(true)))
+        ]))
+    );
+
+    assert!(Type::Dictionary {
+        key: Rc::new(Type::Int(None)),
+        value: Rc::new(Type::Object),
+    }
+    .subsumes(
+        ctx,
+        &Type::ExactDictionary {
+            entries: vec![(Type::Int(Some(1)), Type::Null)]
+        }
+    ));
+}
+
+#[test]
+fn display() {
+    let code = Rc::new(String::from("a"));
+    let a = Slice::new(code);
+
+    assert_eq!(Type::Int(Some(5)).to_string(), "int (literal 5)");
+    assert_eq!(
+        Type::Array {
+            element: Rc::new(Type::Int(None))
+        }
+        .to_string(),
+        "Array[int]"
+    );
+    assert_eq!(
+        Type::Dictionary {
+            key: Rc::new(Type::String(None)),
+            value: Rc::new(Type::NonNull {
+                inner: Rc::new(Type::Object)
+            })
+        }
+        .to_string(),
+        "Dictionary[String, Object!]"
+    );
+    assert_eq!(
+        Type::ExactArray {
+            members: vec![Type::Int(Some(1))]
+        }
+        .annotation(),
+        Some(String::from("Array"))
+    );
+    assert_eq!(
+        Type::union([
+            Type::Null,
+            Type::Class {
+                name: Rc::new(String::from("Node"))
+            }
+        ])
+        .annotation(),
+        Some(String::from("Node"))
+    );
+    assert_eq!(
+        Type::union([Type::Int(None), Type::String(None)]).annotation(),
+        None
+    );
+    assert_eq!(
+        Type::ExactDictionary {
+            entries: vec![(Type::String(Some(a)), Type::Int(None))]
+        }
+        .to_string(),
+        "{ \"a\": int }"
+    );
+    assert_eq!(
+        SubsumationIssue::Assignment(vec![
+            (
+                Type::Array {
+                    element: Rc::new(Type::Int(None))
+                },
+                Type::ExactArray {
+                    members: vec![Type::Boolean(Some(true))]
+                }
+            ),
+            (Type::Int(None), Type::Boolean(Some(true)))
+        ])
+        .to_string(),
+        "Type [bool (literal true)] is not assignable to type Array[int]\n  Type bool (literal true) is not assignable to type int"
+    );
+}
+
+#[test]
+fn unions() {
+    let project = GodotProject::for_test();
+    let ctx = SubsumationContext {
+        godot_project: &project,
+    };
+
+    let nullable_int = Type::union([Type::Int(None), Type::Null]);
+    assert_eq!(
+        Type::union([
+            Type::Int(Some(1)),
+            Type::union([Type::Null, Type::Int(None)]),
+            Type::Int(Some(2)),
+            Type::Null
+        ]),
+        nullable_int
+    );
+    assert_eq!(Type::union([Type::Int(None), Type::Any]), Type::Any);
+    assert_eq!(
+        Type::union([Type::Any, Type::Poisoned, Type::Null]),
+        Type::Poisoned
+    );
+    assert_eq!(Type::union([Type::Unknown, Type::Color]), Type::Color);
+    assert_eq!(Type::union([Type::Color, Type::Color]), Type::Color);
+    assert_eq!(nullable_int.to_string(), "int | null");
+
+    assert!(nullable_int.subsumes(ctx, &Type::Null));
+    assert!(nullable_int.subsumes(ctx, &Type::Int(Some(3))));
+    assert!(!nullable_int.subsumes(ctx, &Type::String(None)));
+    assert!(!Type::Int(None).subsumes(ctx, &nullable_int));
+    assert!(Type::Float(None).subsumes(ctx, &Type::union([Type::Int(None), Type::Float(None)])));
+    assert!(Type::union([Type::Float(None), Type::Null, Type::Color]).subsumes(ctx, &nullable_int));
+}
+
+#[test]
+fn narrowing() {
+    let project = GodotProject::for_test();
+    let ctx = SubsumationContext {
+        godot_project: &project,
+    };
+
+    let maybe_int = Type::union([Type::Int(None), Type::String(None), Type::Null]);
+    assert_eq!(maybe_int.narrow_to(ctx, &Type::Int(None)), Type::Int(None));
+    assert_eq!(
+        maybe_int.narrow_away(ctx, &Type::Int(None)),
+        Type::union([Type::String(None), Type::Null])
+    );
+    assert_eq!(
+        maybe_int.non_null(),
+        Type::union([Type::Int(None), Type::String(None)])
+    );
+    assert_eq!(Type::Any.narrow_to(ctx, &Type::Vector2), Type::Vector2);
+    assert_eq!(
+        Type::Object.non_null(),
+        Type::NonNull {
+            inner: Rc::new(Type::Object)
+        }
+    );
+}
+
+#[test]
+fn classes() {
+    let project = GodotProject::for_test();
+    let ctx = SubsumationContext {
+        godot_project: &project,
+    };
+    let class = |name: &str| Type::Class {
+        name: Rc::new(name.to_owned()),
+    };
+
+    assert!(class("Node").subsumes(ctx, &class("Sprite2D")));
+    assert!(class("CanvasItem").subsumes(ctx, &class("Sprite2D")));
+    assert!(!class("Sprite2D").subsumes(ctx, &class("Node2D")));
+    assert!(!class("Node3D").subsumes(ctx, &class("Sprite2D")));
+    assert!(class("Node2D").subsumes(ctx, &Type::Null));
+    assert!(Type::Object.subsumes(ctx, &class("Resource")));
+    assert!(!class("Resource").subsumes(ctx, &Type::Object));
+
+    // assignments accept implicit downcasts, but not unrelated classes
+    assert_eq!(
+        class("Sprite2D").implicit_cast_issues(ctx, &class("Node2D")),
+        None
+    );
+    assert_eq!(
+        class("Resource").implicit_cast_issues(ctx, &Type::union([Type::Object, Type::Null])),
+        None
+    );
+    assert_eq!(
+        class("Node3D").implicit_cast_issues(ctx, &class("Sprite2D")),
+        Some(SubsumationIssue::Assignment(vec![(
+            class("Node3D"),
+            class("Sprite2D")
+        )]))
+    );
+    assert_eq!(
+        Type::union([class("Node"), Type::Null]).narrow_to(ctx, &class("Sprite2D")),
+        Type::NonNull {
+            inner: Rc::new(class("Sprite2D"))
+        }
+    );
+}
+
+#[test]
+fn variant_types() {
+    let project = GodotProject::for_test();
+    let ctx = SubsumationContext {
+        godot_project: &project,
+    };
+    let code = Rc::new(String::from("a"));
+    let a = Slice::new(code);
+
+    assert!(Type::StringName.subsumes(ctx, &Type::String(Some(a))));
+    assert!(Type::String(None).subsumes(ctx, &Type::StringName));
+    assert!(!Type::StringName.subsumes(ctx, &Type::NodePath));
+    assert!(!Type::Vector4.subsumes(ctx, &Type::Vector4i));
+
+    assert!(Type::PackedInt32Array.subsumes(
+        ctx,
+        &Type::ExactArray {
+            members: vec![Type::Int(Some(1)), Type::Int(Some(2))]
+        }
+    ));
+    assert!(!Type::PackedVector2Array.subsumes(
+        ctx,
+        &Type::ExactArray {
+            members: vec![Type::Vector3]
+        }
+    ));
+    assert!(!Type::PackedByteArray.subsumes(
+        ctx,
+        &Type::Array {
+            element: Rc::new(Type::Int(None))
+        }
+    ));
+
+    assert_eq!(Type::PackedStringArray.to_string(), "PackedStringArray");
+    assert_eq!(Type::Quaternion.to_string(), "Quaternion");
 }
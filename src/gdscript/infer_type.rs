@@ -1,8 +1,43 @@
-use crate::godot_project::GodotProject;
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    extension_api::ast::EngineMethod,
+    godot_project::{GodotProject, SceneRoot},
+};
 
 use super::ast::*;
 use super::check::CheckContext;
+use super::const_eval::literal_as;
 use super::gd_type::Type;
+use super::operators::{binary_operation_type, unary_operation_type};
+use super::resolve_type::{resolve_api_type, resolve_type_name};
+use super::returns::always_returns;
+
+thread_local! {
+    /// Declarations whose types are currently being inferred, so that
+    /// inference which refers back to itself bottoms out at `Type::Unknown`
+    /// instead of recursing forever
+    static INFERRING: RefCell<Vec<ASTAny>> = const { RefCell::new(Vec::new()) };
+}
+
+fn guard_recursion<F: FnOnce() -> Type>(declaration: &ASTAny, infer: F) -> Type {
+    let already_inferring = INFERRING.with(|inferring| {
+        inferring
+            .borrow()
+            .iter()
+            .any(|other| other.ptr_eq(declaration))
+    });
+
+    if already_inferring {
+        return Type::Unknown;
+    }
+
+    INFERRING.with(|inferring| inferring.borrow_mut().push(declaration.clone()));
+    let res = infer();
+    INFERRING.with(|inferring| inferring.borrow_mut().pop());
+
+    res
+}
 
 #[derive(Clone, Copy, Debug)]
 pub struct InferTypeContext<'a> {
@@ -22,11 +57,12 @@ impl AST<Expression> {
         match self.downcast() {
             Expression::NullLiteral(_) => Type::Null,
             Expression::BooleanLiteral(BooleanLiteral { value }) => Type::Boolean(Some(value)),
+            // literals that don't fit are reported by the checker
             Expression::IntLiteral(IntLiteral { value_raw }) => {
-                Type::Int(Some(value_raw.as_str().parse().unwrap()))
+                Type::Int(value_raw.as_str().parse().ok())
             }
             Expression::FloatLiteral(FloatLiteral { value_raw }) => {
-                Type::Float(Some(value_raw.as_str().parse().unwrap()))
+                Type::Float(value_raw.as_str().parse().ok())
             }
             Expression::StringLiteral(StringLiteral { value, multiline }) => {
                 Type::String(Some(value))
@@ -40,65 +76,1040 @@ impl AST<Expression> {
                     .map(|(key, value)| (key.infer_type(ctx), value.infer_type(ctx)))
                     .collect(),
             },
-            Expression::UnaryOperation(UnaryOperation { op, subject }) => match op.downcast() {
-                UnaryOperator::BitwiseNot => todo!(),
-                UnaryOperator::Negative => todo!(),
-                UnaryOperator::Not => todo!(),
-            },
+            Expression::UnaryOperation(UnaryOperation { op, subject }) => {
+                unary_operation_type(op.downcast(), &subject.infer_type(ctx), ctx)
+                    .unwrap_or(Type::Poisoned)
+            }
             Expression::BinaryOperation(BinaryOperation { op, left, right }) => {
-                let left_type = left.infer_type(ctx);
-                let right_type = right.infer_type(ctx);
-
                 match op.downcast() {
-                    BinaryOperator::Is => todo!(),
-                    BinaryOperator::DoubleStar => todo!(),
-                    BinaryOperator::Star => todo!(),
-                    BinaryOperator::Slash => todo!(),
-                    BinaryOperator::Percent => todo!(),
-                    BinaryOperator::Plus => todo!(),
-                    BinaryOperator::Minus => todo!(),
-                    BinaryOperator::ShiftLeft => todo!(),
-                    BinaryOperator::ShiftRight => todo!(),
-                    BinaryOperator::BitwiseAnd => todo!(),
-                    BinaryOperator::BitwiseXor => todo!(),
-                    BinaryOperator::BitwiseOr => todo!(),
-                    BinaryOperator::Less => Type::Boolean(None),
-                    BinaryOperator::Greater => Type::Boolean(None),
-                    BinaryOperator::Equals => Type::Boolean(None),
-                    BinaryOperator::NotEquals => Type::Boolean(None),
-                    BinaryOperator::GreaterEqual => Type::Boolean(None),
-                    BinaryOperator::LessEqual => Type::Boolean(None),
-                    BinaryOperator::In => Type::Boolean(None),
-                    BinaryOperator::And => todo!(),
-                    BinaryOperator::Or => todo!(),
-                    BinaryOperator::As => todo!(),
+                    BinaryOperator::As => match right.resolve_as_type(ctx.into()) {
+                        // casting an object to a class it isn't gives null
+                        Some(typ @ Type::Class { name: _ }) | Some(typ @ Type::Object) => {
+                            Type::union([typ, Type::Null])
+                        }
+                        Some(typ) => typ,
+                        None => Type::Poisoned,
+                    },
+                    op => binary_operation_type(
+                        op,
+                        &left.infer_type(ctx),
+                        &right.infer_type(ctx),
+                        ctx,
+                    )
+                    .unwrap_or(Type::Poisoned),
                 }
             }
+            Expression::TernaryExpression(TernaryExpression {
+                condition: _,
+                if_true,
+                if_false,
+            }) => Type::union([if_true.infer_type(ctx), if_false.infer_type(ctx)]),
+            Expression::FunctionCall(FunctionCall { function, args }) => {
+                if let Some(callee) = self.callee(ctx) {
+                    return callee.return_type(ctx);
+                }
+
+                match function.downcast() {
+                    Expression::PropertyAccess(PropertyAccess { subject, property }) => {
+                        let property = property.downcast().name;
+
+                        match subject.resolve_as_type(ctx.into()) {
+                            // constructing an instance of a class
+                            Some(typ @ Type::Class { name: _ }) if property.as_str() == "new" => {
+                                typ.non_null()
+                            }
+                            Some(typ) => method_return_type(&typ, property.as_str(), ctx),
+                            None => {
+                                method_return_type(&subject.infer_type(ctx), property.as_str(), ctx)
+                            }
+                        }
+                    }
+                    Expression::LocalIdentifier(LocalIdentifier { name }) => {
+                        let name = name.as_str();
+
+                        // calling a variable holding a Callable
+                        if self.resolve_symbol(name).is_some() {
+                            return Type::Any;
+                        }
+
+                        // calling something undeclared, which has already
+                        // been reported
+                        if global_identifier_type(&function, name, ctx).is_none() {
+                            return Type::Poisoned;
+                        }
+
+                        if name == "range" {
+                            return Type::Array {
+                                element: Rc::new(Type::Int(None)),
+                            };
+                        }
+
+                        if name == "preload" {
+                            return match args.first().map(|arg| arg.infer_type(ctx)) {
+                                Some(Type::String(Some(path))) => preload_type(path.as_str()),
+                                _ => Type::Any,
+                            };
+                        }
+
+                        // calling a type's name constructs one
+                        if let Some(typ) = resolve_type_name(name, ctx.into()) {
+                            return typ;
+                        }
+
+                        match ctx.godot_project.engine_api.utility_functions.get(name) {
+                            Some(function) => api_return_type(function, ctx),
+                            None => method_return_type(&self_type(self, ctx), name, ctx),
+                        }
+                    }
+                    _ => Type::Any,
+                }
+            }
+            Expression::PropertyAccess(PropertyAccess { subject, property }) => {
+                let property = property.downcast().name;
+
+                if let Some(declaration) = subject.named_enum() {
+                    return enum_variant_type(&declaration, property.as_str(), ctx)
+                        .unwrap_or(Type::Poisoned);
+                }
+
+                match subject.resolve_as_type(ctx.into()) {
+                    Some(typ) => constant_type(&typ, property.as_str(), ctx),
+                    None => member_type(&subject.infer_type(ctx), property.as_str(), ctx),
+                }
+            }
+            Expression::Subscript(Subscript { subject, index }) => {
+                subscript_type(&subject.infer_type(ctx), &index.infer_type(ctx), ctx)
+                    .unwrap_or(Type::Poisoned)
+            }
+            Expression::Lambda(_) => Type::Callable,
             Expression::LocalIdentifier(LocalIdentifier { name }) => {
-                let resolved = self.resolve_symbol(name.as_str());
-
-                match resolved.as_ref().map(|ast| ast.details()) {
-                    Some(Any::ValueDeclaration(ValueDeclaration {
-                        is_const,
-                        name,
-                        declared_type,
-                        value,
-                    })) => declared_type
+                match self.resolve_symbol(name.as_str()) {
+                    Some(resolved) => {
+                        let declared = declaration_type(&resolved, name.as_str(), ctx);
+
+                        self.narrow_type(&resolved, declared, ctx)
+                    }
+                    None => {
+                        global_identifier_type(self, name.as_str(), ctx).unwrap_or(Type::Poisoned)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl AST<FuncDeclaration> {
+    /// The type calling this function gives: its declared return type, or
+    /// else the union of everything it returns. A function that never
+    /// returns a value is `void`, and one that only returns a value on some
+    /// paths may also give `null`.
+    pub fn return_type<'a>(&self, ctx: InferTypeContext<'a>) -> Type {
+        let FuncDeclaration {
+            is_static: _,
+            name: _,
+            args: _,
+            return_type,
+            body,
+        } = self.downcast();
+
+        if let Some(return_type) = return_type {
+            return return_type.resolve_type(ctx.into());
+        }
+
+        guard_recursion(&self.clone().upcast(), || {
+            let returns = self.returns();
+            let values: Vec<Type> = returns
+                .iter()
+                .filter_map(|ret| ret.downcast().expr)
+                .map(|expr| expr.infer_type(ctx).widen())
+                .collect();
+
+            if values.is_empty() {
+                return Type::Void;
+            }
+
+            let returns_nothing =
+                returns.iter().any(|ret| ret.downcast().expr.is_none()) || !always_returns(&body);
+
+            Type::union(
+                values
+                    .into_iter()
+                    .chain(returns_nothing.then_some(Type::Null)),
+            )
+        })
+    }
+}
+
+/// The type of the value that `declaration` binds `name` to
+fn declaration_type<'a>(declaration: &ASTAny, name: &str, ctx: InferTypeContext<'a>) -> Type {
+    match declaration.details() {
+        Any::ValueDeclaration(ValueDeclaration {
+            is_const,
+            name: _,
+            declared_type,
+            is_inferred,
+            value,
+        }) => match declared_type {
+            Some(declared_type) => {
+                let declared_type = declared_type.resolve_type(ctx.into());
+
+                // a typed constant still has a known value
+                match value.as_ref().filter(|_| *is_const) {
+                    Some(value) => {
+                        let value_type = guard_recursion(declaration, || value.infer_type(ctx));
+
+                        literal_as(&value_type, &declared_type).unwrap_or(declared_type)
+                    }
+                    None => declared_type,
+                }
+            }
+            None => {
+                guard_recursion(declaration, || {
+                    let value_type = value
                         .as_ref()
-                        .map(|t| t.resolve_type(ctx.into()))
-                        .unwrap_or_else(|| {
-                            value
-                                .as_ref()
-                                .map(|v| v.infer_type(ctx))
-                                .unwrap_or(Type::Poisoned)
-                        }),
-                    _ => Type::Poisoned,
+                        .map(|v| v.infer_type(ctx))
+                        .unwrap_or(Type::Null);
+
+                    if *is_const {
+                        value_type
+                    } else if *is_inferred {
+                        // `:=` fixes the variable's type, but not
+                        // its value
+                        value_type.widen()
+                    } else {
+                        // an untyped variable can hold anything
+                        // that is ever assigned to it
+                        Type::union(
+                            std::iter::once(value_type)
+                                .chain(
+                                    assignments_to(declaration, name)
+                                        .into_iter()
+                                        .map(|value| value.infer_type(ctx)),
+                                )
+                                .map(|typ| typ.widen()),
+                        )
+                    }
+                })
+            }
+        },
+        Any::EnumDeclaration(EnumDeclaration {
+            name: Some(_),
+            variants: _,
+        }) => enum_type(),
+        Any::EnumDeclaration(_) => {
+            enum_variant_type(declaration, name, ctx).unwrap_or(Type::Poisoned)
+        }
+        Any::ForLoop(ForLoop {
+            item_name: _,
+            item_type,
+            iteree,
+            body: _,
+        }) => match item_type {
+            Some(item_type) => item_type.resolve_type(ctx.into()),
+            None => iteration_type(&iteree.infer_type(ctx)).unwrap_or(Type::Poisoned),
+        },
+        Any::FuncArg(FuncArg {
+            name: _,
+            declared_type: Some(declared_type),
+            default_value: _,
+        }) => declared_type.resolve_type(ctx.into()),
+        Any::FuncDeclaration(_) => Type::Callable,
+        Any::SignalDeclaration(_) => Type::Signal,
+        // a binding that makes up a whole pattern matches the subject itself
+        Any::BindingPattern(_) => match declaration
+            .parent()
+            .and_then(|arm| arm.parent())
+            .map(|parent| parent.details().clone())
+        {
+            Some(Any::MatchStatement(MatchStatement { subject, arms: _ })) => {
+                subject.infer_type(ctx)
+            }
+            _ => Type::Any,
+        },
+        // an inner class, as a value, is the script object for that class
+        Any::ClassDeclaration(_) => Type::Class {
+            name: Rc::new(String::from("GDScript")),
+        }
+        .non_null(),
+        _ => Type::Any,
+    }
+}
+
+/// The type of the loop variable when iterating over a value of type
+/// `iteree`, or `None` if it can't be iterated over
+pub fn iteration_type(iteree: &Type) -> Option<Type> {
+    match iteree {
+        // objects can implement their own iterators
+        Type::Any | Type::Unknown | Type::Object | Type::Class { name: _ } => Some(Type::Any),
+        Type::Poisoned => Some(Type::Poisoned),
+        Type::NonNull { inner } => iteration_type(inner),
+        Type::Union { members } => members
+            .iter()
+            .map(iteration_type)
+            .collect::<Option<Vec<Type>>>()
+            .map(Type::union),
+
+        // counting up to a number
+        Type::Int(_) => Some(Type::Int(None)),
+        Type::Float(_) => Some(Type::Float(None)),
+
+        Type::String(_) => Some(Type::String(None)),
+        Type::Array { element } => Some(element.as_ref().clone()),
+        Type::ExactArray { members } => Some(Type::union(members.iter().map(Type::widen))),
+        Type::Dictionary { key, value: _ } => Some(key.as_ref().clone()),
+        Type::ExactDictionary { entries } => {
+            Some(Type::union(entries.iter().map(|(key, _)| key.widen())))
+        }
+        other => other.packed_element(),
+    }
+}
+
+/// The type of `subject[index]`, or `None` if values of type `subject` can't
+/// be indexed with an `index`
+pub fn subscript_type<'a>(subject: &Type, index: &Type, ctx: InferTypeContext<'a>) -> Option<Type> {
+    let int_index = matches!(
+        index.widen(),
+        Type::Int(None) | Type::Any | Type::Unknown | Type::Poisoned
+    );
+
+    match subject {
+        Type::Poisoned => Some(Type::Poisoned),
+        Type::Any | Type::Unknown => Some(Type::Any),
+        Type::NonNull { inner } => subscript_type(inner, index, ctx),
+        Type::Union { members } => members
+            .iter()
+            .map(|member| subscript_type(member, index, ctx))
+            .collect::<Option<Vec<Type>>>()
+            .map(Type::union),
+
+        // properties can be looked up by name
+        Type::Object | Type::Class { name: _ } => Some(Type::Any),
+
+        Type::Array { element } => int_index.then(|| element.as_ref().clone()),
+        Type::ExactArray { members } => {
+            let all_members = || Type::union(members.iter().map(Type::widen));
+
+            match index {
+                // negative indices count from the end
+                Type::Int(Some(index)) => {
+                    let position = if *index < 0 {
+                        *index + members.len() as i64
+                    } else {
+                        *index
+                    };
+
+                    Some(
+                        usize::try_from(position)
+                            .ok()
+                            .and_then(|position| members.get(position))
+                            .map(Type::widen)
+                            .unwrap_or_else(all_members),
+                    )
+                }
+                _ => int_index.then(all_members),
+            }
+        }
+        Type::Dictionary { key: _, value } => Some(value.as_ref().clone()),
+        Type::ExactDictionary { entries } => Some(
+            entries
+                .iter()
+                .find(|(key, _)| key == index)
+                .map(|(_, value)| value.clone())
+                .unwrap_or_else(|| Type::union(entries.iter().map(|(_, value)| value.widen()))),
+        ),
+        other => {
+            let builtin = ctx
+                .godot_project
+                .engine_api
+                .builtin_class(other.api_name()?)?;
+            let return_type = builtin.indexing_return_type.as_deref()?;
+
+            (builtin.is_keyed || int_index).then(|| resolve_api_type(return_type, ctx.into()))
+        }
+    }
+}
+
+/// The type every value stored in the variable declared by `declaration` has
+/// to fit, or `None` if the variable is dynamically typed
+pub fn variable_type<'a>(declaration: &ASTAny, ctx: InferTypeContext<'a>) -> Option<Type> {
+    match declaration.details() {
+        Any::ValueDeclaration(ValueDeclaration {
+            is_const: _,
+            name: _,
+            declared_type: Some(declared_type),
+            is_inferred: _,
+            value: _,
+        }) => Some(declared_type.resolve_type(ctx.into())),
+        Any::ValueDeclaration(ValueDeclaration {
+            is_const: _,
+            name: _,
+            declared_type: None,
+            is_inferred: true,
+            value: Some(value),
+        }) => Some(guard_recursion(declaration, || {
+            value.infer_type(ctx).widen()
+        })),
+        Any::FuncArg(FuncArg {
+            name: _,
+            declared_type: Some(declared_type),
+            default_value: _,
+        }) => Some(declared_type.resolve_type(ctx.into())),
+        Any::ForLoop(ForLoop {
+            item_name: _,
+            item_type: Some(item_type),
+            iteree: _,
+            body: _,
+        }) => Some(item_type.resolve_type(ctx.into())),
+        _ => None,
+    }
+}
+
+/// All values assigned (with a plain `=`) to the variable declared by
+/// `declaration` anywhere in its scope
+fn assignments_to(declaration: &ASTAny, name: &str) -> Vec<AST<Expression>> {
+    let scope_node = match declaration.parent() {
+        Some(scope_node) => scope_node,
+        None => return Vec::new(),
+    };
+
+    match scope_node.scope() {
+        // a later declaration of the same name isn't the one assignments
+        // refer to
+        Some(scope)
+            if scope
+                .lookup(name, usize::MAX)
+                .is_some_and(|found| found.ptr_eq(declaration)) =>
+        {
+            scope.assignments(&scope_node, name).to_vec()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// If `node` is a plain `=` assignment to the variable declared by
+/// `declaration`, the value being assigned
+pub fn assigned_value(node: &ASTAny, declaration: &ASTAny, name: &str) -> Option<AST<Expression>> {
+    match node.details() {
+        Any::AssignmentStatement(AssignmentStatement {
+            target,
+            value,
+            operator: None,
+        }) if target.refers_to(declaration, name) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+impl AST<Expression> {
+    /// Whether this expression is an identifier referring to the variable
+    /// declared by `declaration`
+    pub fn refers_to(&self, declaration: &ASTAny, name: &str) -> bool {
+        match self.downcast() {
+            Expression::LocalIdentifier(LocalIdentifier { name: identifier }) => {
+                identifier.as_str() == name
+                    && self
+                        .resolve_symbol(name)
+                        .is_some_and(|resolved| resolved.ptr_eq(declaration))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl AST<Expression> {
+    /// If this expression is the name of an enum declared in a script, that
+    /// enum's declaration
+    pub fn named_enum(&self) -> Option<ASTAny> {
+        match self.downcast() {
+            Expression::LocalIdentifier(LocalIdentifier { name }) => {
+                self.resolve_symbol(name.as_str()).filter(|resolved| {
+                    matches!(
+                        resolved.details(),
+                        Any::EnumDeclaration(EnumDeclaration {
+                            name: Some(_),
+                            variants: _,
+                        })
+                    )
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Enums are dictionaries from member names to values
+fn enum_type() -> Type {
+    Type::Dictionary {
+        key: Rc::new(Type::String(None)),
+        value: Rc::new(Type::Int(None)),
+    }
+}
+
+/// The type of the member `variant` of an enum declared in a script. Members
+/// without an explicit value count up from the one before them, so they
+/// have a literal value as long as every value before them does.
+fn enum_variant_type<'a>(
+    declaration: &ASTAny,
+    variant: &str,
+    ctx: InferTypeContext<'a>,
+) -> Option<Type> {
+    let variants = match declaration.details() {
+        Any::EnumDeclaration(EnumDeclaration { name: _, variants }) => variants,
+        _ => return None,
+    };
+
+    let typ = guard_recursion(declaration, || {
+        let mut next = Some(0);
+
+        for (name, value) in variants {
+            let current = match value.as_ref().map(|value| value.infer_type(ctx)) {
+                Some(Type::Int(value)) => value,
+                Some(_) => None,
+                None => next,
+            };
+
+            if name.downcast().name.as_str() == variant {
+                return Type::Int(current);
+            }
+
+            next = current.and_then(|current| current.checked_add(1));
+        }
+
+        Type::Poisoned
+    });
+
+    match typ {
+        Type::Poisoned => None,
+        other => Some(other),
+    }
+}
+
+/// The type of the resource loaded by `preload()`ing `path`, going by its
+/// file extension
+fn preload_type(path: &str) -> Type {
+    let extension = path.rsplit_once('.').map(|(_, extension)| extension);
+
+    let class = match extension {
+        Some("gd") => "GDScript",
+        Some("tscn" | "scn") => "PackedScene",
+        Some("png" | "jpg" | "jpeg" | "svg" | "webp" | "bmp" | "tga") => "Texture2D",
+        Some("wav" | "ogg" | "mp3") => "AudioStream",
+        Some("gdshader") => "Shader",
+        Some("ttf" | "otf" | "woff" | "woff2") => "Font",
+        _ => "Resource",
+    };
+
+    Type::Class {
+        name: Rc::new(String::from(class)),
+    }
+    .non_null()
+}
+
+impl AST<Expression> {
+    /// If this expression is a call to a function declared in a script,
+    /// that function's declaration. Functions are looked up in the
+    /// enclosing scopes, then in the scripts the subject's class (or the
+    /// current script) inherits from.
+    pub fn callee<'a>(&self, ctx: InferTypeContext<'a>) -> Option<AST<FuncDeclaration>> {
+        let FunctionCall { function, args: _ } = self.try_downcast::<FunctionCall>()?;
+
+        match function.downcast() {
+            Expression::LocalIdentifier(LocalIdentifier { name }) => {
+                match self.resolve_symbol(name.as_str()) {
+                    Some(resolved) => resolved.try_recast::<FuncDeclaration>(),
+                    None => script_method(&self_type(self, ctx), name.as_str(), ctx),
                 }
             }
+            Expression::PropertyAccess(PropertyAccess { subject, property }) => {
+                let subject_type = subject
+                    .resolve_as_type(ctx.into())
+                    .unwrap_or_else(|| subject.infer_type(ctx));
+
+                script_method(&subject_type, property.downcast().name.as_str(), ctx)
+            }
+            _ => None,
         }
     }
 }
 
+/// The function `name` declared by the script class `subject` or one of
+/// the script classes it inherits from
+fn script_method<'a>(
+    subject: &Type,
+    name: &str,
+    ctx: InferTypeContext<'a>,
+) -> Option<AST<FuncDeclaration>> {
+    let class = match subject {
+        Type::NonNull { inner } => return script_method(inner, name, ctx),
+        Type::Class { name } => name,
+        _ => return None,
+    };
+
+    ctx.godot_project.find_in_script_classes(class, |script| {
+        script
+            .downcast()
+            .declarations
+            .into_iter()
+            .filter_map(|decl| decl.upcast().try_recast::<FuncDeclaration>())
+            .find(|func| func.downcast().name.downcast().name.as_str() == name)
+    })
+}
+
+/// Functions built into GDScript itself, on top of the engine's utility
+/// functions
+pub const GDSCRIPT_FUNCTIONS: &[&str] = &[
+    "Color8",
+    "assert",
+    "char",
+    "convert",
+    "dict_to_inst",
+    "get_stack",
+    "inst_to_dict",
+    "is_instance_of",
+    "len",
+    "load",
+    "preload",
+    "print_debug",
+    "print_stack",
+    "range",
+    "type_exists",
+];
+
+/// Constants built into GDScript itself
+fn gdscript_constant_type(name: &str) -> Option<Type> {
+    match name {
+        "PI" => Some(Type::Float(Some(std::f64::consts::PI))),
+        "TAU" => Some(Type::Float(Some(std::f64::consts::TAU))),
+        "INF" => Some(Type::Float(Some(f64::INFINITY))),
+        // NaN isn't equal to itself, so it can't be a literal type
+        "NAN" => Some(Type::Float(None)),
+        _ => None,
+    }
+}
+
+/// Identifiers that aren't declared in any scope enclosing `node`:
+/// members inherited by the script, autoloads, singletons, global constants
+/// and class names, and GDScript's own functions and constants. `None` if the name
+/// isn't declared anywhere.
+pub fn global_identifier_type<'a>(
+    node: &AST<Expression>,
+    name: &str,
+    ctx: InferTypeContext<'a>,
+) -> Option<Type> {
+    let engine_api = &ctx.godot_project.engine_api;
+    let self_type = self_type(node, ctx);
+
+    if name == "self" {
+        return Some(self_type);
+    }
+
+    if let Some(member_type) = inherited_member_type(&self_type, name, ctx) {
+        return Some(member_type);
+    }
+
+    if let Some(autoload_type) = autoload_type(name, ctx) {
+        return Some(autoload_type);
+    }
+
+    if let Some(class) = engine_api.singletons.get(name) {
+        return Some(
+            Type::Class {
+                name: Rc::new(class.clone()),
+            }
+            .non_null(),
+        );
+    }
+
+    if let Some(value) = engine_api.global_constant(name) {
+        return Some(Type::Int(Some(value)));
+    }
+
+    if let Some(constant_type) = gdscript_constant_type(name) {
+        return Some(constant_type);
+    }
+
+    if engine_api.utility_functions.contains_key(name) || GDSCRIPT_FUNCTIONS.contains(&name) {
+        return Some(Type::Callable);
+    }
+
+    // a class used as a value, like the subject of `Enemy.new()`
+    if resolve_type_name(name, ctx.into()).is_some() {
+        return Some(Type::Any);
+    }
+
+    None
+}
+
+/// Every name an identifier at `node` could refer to without being
+/// declared in its scopes, for suggesting what a misspelled name meant.
+/// Global enum values and class names are left out, since there are too
+/// many of them for a close match to mean much.
+pub fn global_identifier_names<'a>(
+    node: &AST<Expression>,
+    ctx: InferTypeContext<'a>,
+) -> Vec<String> {
+    let godot_project = ctx.godot_project;
+    let engine_api = &godot_project.engine_api;
+    let mut groups: Vec<Vec<String>> = vec![vec![String::from("self")]];
+
+    // inherited members come first, nearest class first
+    if let Some(class) = class_name_of(&self_type(node, ctx)) {
+        for ancestor in godot_project.ancestors(class) {
+            if let Some(scope) = godot_project
+                .script_class(&ancestor)
+                .and_then(|script| script.scope())
+            {
+                groups.push(scope.names().map(String::from).collect());
+            }
+
+            if let Some(engine_class) = engine_api.class(&ancestor) {
+                groups.push(
+                    engine_class
+                        .properties
+                        .keys()
+                        .chain(engine_class.methods.keys())
+                        .chain(engine_class.signals.keys())
+                        .chain(engine_class.constants.keys())
+                        .cloned()
+                        .collect(),
+                );
+            }
+        }
+    }
+
+    groups.push(
+        godot_project
+            .metadata
+            .autoloads()
+            .into_iter()
+            .filter(|autoload| autoload.enabled)
+            .map(|autoload| autoload.name)
+            .collect(),
+    );
+    groups.push(engine_api.singletons.keys().cloned().collect());
+    groups.push(
+        engine_api
+            .utility_functions
+            .keys()
+            .cloned()
+            .chain(GDSCRIPT_FUNCTIONS.iter().map(|name| String::from(*name)))
+            .chain(["PI", "TAU", "INF", "NAN"].map(String::from))
+            .collect(),
+    );
+
+    // the API's tables are unordered, but lists of names shouldn't be
+    groups
+        .into_iter()
+        .flat_map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect()
+}
+
+/// The type of the autoload singleton `name`: an instance of its script's
+/// class, or for a scene, of its root node's
+fn autoload_type<'a>(name: &str, ctx: InferTypeContext<'a>) -> Option<Type> {
+    let godot_project = ctx.godot_project;
+    let autoload = godot_project
+        .metadata
+        .autoloads()
+        .into_iter()
+        .find(|autoload| autoload.enabled && autoload.name == name)?;
+    let script_class = |path: &str| {
+        godot_project
+            .script_at(path)
+            .and_then(|script| godot_project.script_class_name(script))
+    };
+
+    let class = match godot_project.script_index.scene_roots.get(&autoload.path) {
+        Some(SceneRoot { node_type, script }) => script
+            .as_deref()
+            .and_then(script_class)
+            .or_else(|| node_type.clone().map(Rc::new)),
+        None => script_class(&autoload.path),
+    };
+
+    // the autoload exists even if we can't tell what it is
+    Some(match class {
+        Some(name) => Type::Class { name }.non_null(),
+        None => Type::Any,
+    })
+}
+
+/// The member `name` that the class `typ` declares or inherits, whether
+/// from scripts or from engine classes. Unlike `member_type`, this is only
+/// `None` if the member definitely doesn't exist.
+fn inherited_member_type<'a>(typ: &Type, name: &str, ctx: InferTypeContext<'a>) -> Option<Type> {
+    let godot_project = ctx.godot_project;
+    let class = class_name_of(typ)?;
+
+    if let Some(declaration) =
+        godot_project.find_in_script_classes(class, |script| script.declaration(name))
+    {
+        return Some(declaration_type(&declaration, name, ctx));
+    }
+
+    godot_project.find_in_engine_classes(class, |engine_class| {
+        if let Some(property) = engine_class.properties.get(name) {
+            Some(resolve_api_type(&property.prop_type, ctx.into()))
+        } else if engine_class.methods.contains_key(name) {
+            Some(Type::Callable)
+        } else if engine_class.signals.contains_key(name) {
+            Some(Type::Signal)
+        } else if let Some(value) = engine_class.constant(name) {
+            Some(Type::Int(Some(value)))
+        } else if engine_class.enums.contains_key(name) {
+            Some(enum_type())
+        } else {
+            None
+        }
+    })
+}
+
+pub fn class_name_of(typ: &Type) -> Option<&str> {
+    match typ {
+        Type::NonNull { inner } => class_name_of(inner),
+        Type::Class { name } => Some(name.as_str()),
+        _ => None,
+    }
+}
+
+/// The type of `self` in the script or inner class containing `node`
+pub fn self_type<'a>(node: &AST<Expression>, ctx: InferTypeContext<'a>) -> Type {
+    // an inner class doesn't share the script's instance, so `self` there
+    // is only whatever the inner class itself extends
+    if let Some(class) = node
+        .clone()
+        .upcast()
+        .find_parent_of_type::<ClassDeclaration>()
+    {
+        return Type::Class {
+            name: Rc::new(
+                extended_class(&class.downcast().declarations)
+                    .unwrap_or_else(|| String::from("RefCounted")),
+            ),
+        }
+        .non_null();
+    }
+
+    let script = node.clone().upcast().find_parent_of_type::<GDScript>();
+
+    // scripts that are part of the project are their own class, whether
+    // or not they declare a class_name
+    if let Some(name) = script
+        .as_ref()
+        .and_then(|script| ctx.godot_project.script_class_name(script))
+    {
+        return Type::Class { name }.non_null();
+    }
+
+    let declarations = script
+        .map(|script| script.downcast().declarations)
+        .unwrap_or_default();
+
+    let class_name = declarations.iter().find_map(|decl| match decl.downcast() {
+        Declaration::ClassNameDeclaration(ClassNameDeclaration { class_name }) => {
+            Some(class_name.downcast().name.as_str().to_owned())
+        }
+        _ => None,
+    });
+
+    Type::Class {
+        name: Rc::new(
+            class_name
+                .or_else(|| extended_class(&declarations))
+                .unwrap_or_else(|| String::from("RefCounted")),
+        ),
+    }
+    .non_null()
+}
+
+/// The class named by the `extends` among `declarations`, if there is one
+fn extended_class(declarations: &[AST<Declaration>]) -> Option<String> {
+    declarations.iter().find_map(|decl| match decl.downcast() {
+        Declaration::ExtendsDeclaration(ExtendsDeclaration { extends_class }) => {
+            Some(extends_class.downcast().name.as_str().to_owned())
+        }
+        _ => None,
+    })
+}
+
+/// The type of the property or member `name` on a value of type `subject`
+pub fn member_type<'a>(subject: &Type, name: &str, ctx: InferTypeContext<'a>) -> Type {
+    let godot_project = ctx.godot_project;
+
+    if *subject == Type::Poisoned {
+        return Type::Poisoned;
+    }
+
+    if let Type::Union { members } = subject {
+        return Type::union(
+            members
+                .iter()
+                .filter(|member| **member != Type::Null)
+                .map(|member| member_type(member, name, ctx)),
+        );
+    }
+
+    let class = match subject.api_name() {
+        Some(class) => class,
+        None => return Type::Any,
+    };
+
+    if let Some(builtin) = godot_project.engine_api.builtin_class(class) {
+        return match builtin.members.get(name) {
+            Some(member_type) => resolve_api_type(member_type, ctx.into()),
+            None => Type::Poisoned,
+        };
+    }
+
+    if let Some(declaration) =
+        godot_project.find_in_script_classes(class, |script| script.declaration(name))
+    {
+        return declaration_type(&declaration, name, ctx);
+    }
+
+    if !godot_project.class_exists(class) {
+        return Type::Any;
+    }
+
+    let property = godot_project.find_in_engine_classes(class, |engine_class| {
+        engine_class
+            .properties
+            .get(name)
+            .map(|property| property.prop_type.clone())
+    });
+
+    let is_method_or_signal = godot_project
+        .find_in_engine_classes(class, |engine_class| {
+            (engine_class.methods.contains_key(name) || engine_class.signals.contains_key(name))
+                .then_some(())
+        })
+        .is_some();
+
+    match property {
+        Some(property_type) => resolve_api_type(&property_type, ctx.into()),
+        None if is_method_or_signal => Type::Any,
+        None => {
+            // scripts can declare members we don't model yet (like
+            // signals), so only an all-engine class can tell us the member
+            // doesn't exist
+            if godot_project.engine_api.class(class).is_some() {
+                Type::Poisoned
+            } else {
+                Type::Any
+            }
+        }
+    }
+}
+
+/// The type of the constant `name` accessed on the type itself, like
+/// `Vector2.ZERO` or `Node.NOTIFICATION_READY`
+fn constant_type<'a>(typ: &Type, name: &str, ctx: InferTypeContext<'a>) -> Type {
+    let godot_project = ctx.godot_project;
+
+    if *typ == Type::Poisoned {
+        return Type::Poisoned;
+    }
+    let class = match typ.api_name() {
+        Some(class) => class,
+        None => return Type::Any,
+    };
+
+    if let Some(builtin) = godot_project.engine_api.builtin_class(class) {
+        if let Some(constant_type) = builtin.constants.get(name) {
+            return resolve_api_type(constant_type, ctx.into());
+        }
+
+        return Type::Poisoned;
+    }
+
+    if let Some(declaration) =
+        godot_project.find_in_script_classes(class, |script| script.declaration(name))
+    {
+        return declaration_type(&declaration, name, ctx);
+    }
+
+    let found = godot_project.find_in_engine_classes(class, |engine_class| {
+        if let Some(value) = engine_class.constant(name) {
+            Some(Type::Int(Some(value)))
+        } else if engine_class.enums.contains_key(name) {
+            Some(enum_type())
+        } else {
+            None
+        }
+    });
+
+    match found {
+        Some(found) => found,
+        None if godot_project.engine_api.class(class).is_some() => Type::Poisoned,
+        None => Type::Any,
+    }
+}
+
+/// The return type of calling the method `method` on a value of type
+/// `subject`
+fn method_return_type<'a>(subject: &Type, method: &str, ctx: InferTypeContext<'a>) -> Type {
+    let godot_project = ctx.godot_project;
+
+    if *subject == Type::Poisoned {
+        return Type::Poisoned;
+    }
+
+    if let Type::Union { members } = subject {
+        return Type::union(
+            members
+                .iter()
+                .filter(|member| **member != Type::Null)
+                .map(|member| method_return_type(member, method, ctx)),
+        );
+    }
+
+    if let Some(typ) = builtin_method_return_type(subject, method) {
+        return typ;
+    }
+
+    let class = match subject.api_name() {
+        Some(class) => class,
+        None => return Type::Any,
+    };
+
+    let found = match godot_project.engine_api.builtin_class(class) {
+        Some(builtin) => builtin.methods.get(method).cloned(),
+        None => godot_project.find_in_engine_classes(class, |engine_class| {
+            engine_class.methods.get(method).cloned()
+        }),
+    };
+
+    match found {
+        Some(found) => api_return_type(&found, ctx),
+        None => Type::Any,
+    }
+}
+
+fn api_return_type<'a>(method: &EngineMethod, ctx: InferTypeContext<'a>) -> Type {
+    match &method.return_type {
+        Some(return_type) => resolve_api_type(return_type, ctx.into()),
+        None => Type::Void,
+    }
+}
+
+/// Return types for the built-in methods whose results need more precision
+/// than the engine API gives, like the ones which produce `null` when a
+/// collection is empty
+fn builtin_method_return_type(subject: &Type, method: &str) -> Option<Type> {
+    match (subject, method) {
+        (
+            Type::Array { element },
+            "pop_back" | "pop_front" | "pop_at" | "back" | "front" | "pick_random",
+        ) => Some(Type::union([element.as_ref().clone(), Type::Null])),
+        (Type::Dictionary { key: _, value }, "get") => {
+            Some(Type::union([value.as_ref().clone(), Type::Null]))
+        }
+        _ => None,
+    }
+}
+
 // impl Src<Expression> {
 //     pub fn infer_type(&self, project: &GodotProject, current_module: &GDScript) -> Src<Type> {
 //         match &self.node {
@@ -141,3 +1152,115 @@ impl AST<Expression> {
 //         }
 //     }
 // }
+
+#[test]
+fn for_loops() {
+    use super::check::{check_source, located};
+
+    assert_eq!(iteration_type(&Type::Int(Some(3))), Some(Type::Int(None)));
+    assert_eq!(
+        iteration_type(&Type::Dictionary {
+            key: Rc::new(Type::StringName),
+            value: Rc::new(Type::Any)
+        }),
+        Some(Type::StringName)
+    );
+    assert_eq!(
+        iteration_type(&Type::PackedVector2Array),
+        Some(Type::Vector2)
+    );
+    assert_eq!(iteration_type(&Type::Vector2), None);
+
+    let code = "func f():\n\tfor x: bool in [true]:\n\t\tpass\n\tfor y: int in [true]:\n\t\tpass\n\tfor z in null:\n\t\tpass\n";
+    assert_eq!(
+        located(check_source(code)),
+        vec![
+            (
+                String::from("int"),
+                String::from("Type bool is not assignable to type int")
+            ),
+            (
+                String::from("null"),
+                String::from("Cannot iterate over a value of type null")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn cross_script_types() {
+    let project = GodotProject::for_test().with_scripts(&[
+        (
+            "/project/enemy.gd",
+            "class_name Enemy\nvar alive: bool = true\nfunc hit():\n\tpass\n",
+        ),
+        (
+            "/project/boss.gd",
+            "extends Enemy\nconst ARMORED: bool = true\n",
+        ),
+    ]);
+    let ctx = InferTypeContext {
+        godot_project: &project,
+    };
+    let class = |name: &str| Type::Class {
+        name: Rc::new(name.to_owned()),
+    };
+
+    let boss = project.script_class("res://boss.gd").unwrap();
+    assert_eq!(
+        project.script_class_name(boss),
+        Some(Rc::new(String::from("res://boss.gd")))
+    );
+    assert_eq!(resolve_type_name("Enemy", ctx.into()), Some(class("Enemy")));
+
+    assert_eq!(
+        member_type(&class("res://boss.gd"), "alive", ctx),
+        Type::Boolean(None)
+    );
+    assert_eq!(member_type(&class("Enemy"), "hit", ctx), Type::Callable);
+    assert_eq!(
+        constant_type(&class("res://boss.gd"), "ARMORED", ctx),
+        Type::Boolean(Some(true))
+    );
+}
+
+#[test]
+fn ternary_unions() {
+    use super::parse::parse_script;
+    use crate::utils::slice::Slice;
+
+    let code = "var a := 1 if true else 2.5\nvar b := 1 if false else (2 if true else 1)\n";
+    let script = parse_script(
+        ModuleID(Rc::new(String::from("test.gd"))),
+        Slice::new(Rc::new(code.to_owned())),
+    )
+    .unwrap();
+    let project = GodotProject::for_test();
+    let ctx = InferTypeContext {
+        godot_project: &project,
+    };
+
+    let types: Vec<Type> = script
+        .downcast()
+        .declarations
+        .iter()
+        .map(|declaration| match declaration.downcast() {
+            Declaration::ValueDeclaration(ValueDeclaration {
+                is_const: _,
+                name: _,
+                declared_type: _,
+                is_inferred: _,
+                value: Some(value),
+            }) => value.infer_type(ctx),
+            _ => panic!("expected a value declaration with a value"),
+        })
+        .collect();
+
+    assert_eq!(
+        types,
+        vec![
+            Type::union([Type::Int(Some(1)), Type::Float(Some(2.5))]),
+            Type::union([Type::Int(Some(1)), Type::Int(Some(2))]),
+        ]
+    );
+}
@@ -1,8 +1,16 @@
+pub mod assignment;
 pub mod ast;
 pub mod check;
+pub mod const_eval;
 pub mod gd_type;
 pub mod infer_type;
+pub mod narrow;
+pub mod operators;
 pub mod parse;
 pub mod resolve;
 pub mod resolve_type;
+pub mod returns;
 pub mod rules;
+pub mod scope;
+pub mod shadowing;
+pub mod variant_source;
@@ -0,0 +1,371 @@
+use super::{
+    ast::*,
+    gd_type::{SubsumationContext, Type},
+    infer_type::{assigned_value, InferTypeContext},
+};
+
+/// Something known to be true about the program state wherever a given
+/// expression gets evaluated
+#[derive(Debug, Clone)]
+enum Guard {
+    Condition {
+        condition: AST<Expression>,
+        holds: bool,
+    },
+    MatchArm {
+        subject: AST<Expression>,
+        arm: AST<MatchArm>,
+    },
+}
+
+impl AST<Expression> {
+    /// Narrow the type of the variable this identifier refers to, based on
+    /// the conditions that must have held for control to reach it
+    pub fn narrow_type<'a>(
+        &self,
+        declaration: &ASTAny,
+        declared: Type,
+        ctx: InferTypeContext<'a>,
+    ) -> Type {
+        let name = match self.downcast() {
+            Expression::LocalIdentifier(LocalIdentifier { name }) => name,
+            _ => return declared,
+        };
+
+        // guards are collected from the inside out, but the outer ones
+        // happen first
+        collect_guards(self.clone().upcast(), declaration, name.as_str())
+            .into_iter()
+            .rev()
+            .fold(declared, |narrowed, guard| match guard {
+                Guard::Condition { condition, holds } => narrow_by_condition(
+                    &condition,
+                    holds,
+                    narrowed,
+                    declaration,
+                    name.as_str(),
+                    ctx,
+                ),
+                Guard::MatchArm { subject, arm } => {
+                    narrow_by_match_arm(&subject, &arm, narrowed, declaration, name.as_str(), ctx)
+                }
+            })
+    }
+}
+
+fn collect_guards(node: ASTAny, declaration: &ASTAny, name: &str) -> Vec<Guard> {
+    let mut guards = Vec::new();
+    let mut child = node;
+
+    while let Some(parent) = child.parent() {
+        match parent.details() {
+            Any::IfElseStatement(IfElseStatement {
+                conditions,
+                default_outcome: _,
+            }) => {
+                // every branch before this one must have been skipped
+                for (condition, body) in conditions {
+                    if condition.ptr_eq(&child) {
+                        break;
+                    }
+
+                    if body.ptr_eq(&child) {
+                        guards.push(Guard::Condition {
+                            condition: condition.clone(),
+                            holds: true,
+                        });
+                        break;
+                    }
+
+                    guards.push(Guard::Condition {
+                        condition: condition.clone(),
+                        holds: false,
+                    });
+                }
+            }
+            Any::WhileLoop(WhileLoop { condition, body }) if body.ptr_eq(&child) => {
+                guards.push(Guard::Condition {
+                    condition: condition.clone(),
+                    holds: true,
+                });
+            }
+            Any::TernaryExpression(TernaryExpression {
+                condition,
+                if_true,
+                if_false,
+            }) if if_true.ptr_eq(&child) || if_false.ptr_eq(&child) => {
+                guards.push(Guard::Condition {
+                    condition: condition.clone(),
+                    holds: if_true.ptr_eq(&child),
+                });
+            }
+            // `and` and `or` short-circuit
+            Any::BinaryOperation(BinaryOperation { op, left, right }) if right.ptr_eq(&child) => {
+                match op.downcast() {
+                    BinaryOperator::And => guards.push(Guard::Condition {
+                        condition: left.clone(),
+                        holds: true,
+                    }),
+                    BinaryOperator::Or => guards.push(Guard::Condition {
+                        condition: left.clone(),
+                        holds: false,
+                    }),
+                    _ => {}
+                }
+            }
+            Any::MatchArm(MatchArm {
+                patterns: _,
+                guard,
+                body,
+            }) => {
+                if body.ptr_eq(&child) {
+                    if let Some(guard) = guard {
+                        guards.push(Guard::Condition {
+                            condition: guard.clone(),
+                            holds: true,
+                        });
+                    }
+                }
+
+                let is_guarded =
+                    body.ptr_eq(&child) || guard.as_ref().is_some_and(|guard| guard.ptr_eq(&child));
+                let statement = parent
+                    .parent()
+                    .and_then(|p| p.try_downcast::<MatchStatement>());
+
+                if let (true, Some(MatchStatement { subject, arms: _ })) = (is_guarded, statement) {
+                    guards.push(Guard::MatchArm {
+                        subject,
+                        arm: parent.clone().try_recast::<MatchArm>().unwrap(),
+                    });
+                }
+            }
+            Any::Block(Block { statements }) => {
+                let index = statements
+                    .iter()
+                    .position(|statement| statement.ptr_eq(&child))
+                    .unwrap_or(0);
+
+                for statement in statements[..index].iter().rev() {
+                    let statement = statement.clone().upcast();
+
+                    // nothing before the declaration can tell us anything,
+                    // and reassignment invalidates everything learned so far
+                    if statement.ptr_eq(declaration) || assigns_to(&statement, declaration, name) {
+                        return guards;
+                    }
+
+                    if let Any::IfElseStatement(if_else) = statement.details() {
+                        guards.extend(early_exit_guards(if_else));
+                    }
+                }
+            }
+            Any::FuncDeclaration(_) | Any::ClassDeclaration(_) | Any::GDScript(_) => {
+                return guards;
+            }
+            _ => {}
+        }
+
+        child = parent;
+    }
+
+    guards
+}
+
+/// After an `if` statement whose first few branches always return, break or
+/// continue, the conditions for those branches must have been false
+fn early_exit_guards(if_else: &IfElseStatement) -> Vec<Guard> {
+    if_else
+        .conditions
+        .iter()
+        .take_while(|(_, body)| always_exits(body))
+        .map(|(condition, _)| Guard::Condition {
+            condition: condition.clone(),
+            holds: false,
+        })
+        .collect()
+}
+
+fn always_exits(block: &AST<Block>) -> bool {
+    block
+        .downcast()
+        .statements
+        .iter()
+        .any(|statement| match statement.downcast() {
+            Statement::Return(_) | Statement::Break(_) | Statement::Continue(_) => true,
+            Statement::IfElseStatement(IfElseStatement {
+                conditions,
+                default_outcome: Some(default_outcome),
+            }) => {
+                conditions.iter().all(|(_, body)| always_exits(body))
+                    && always_exits(&default_outcome)
+            }
+            _ => false,
+        })
+}
+
+fn assigns_to(node: &ASTAny, declaration: &ASTAny, name: &str) -> bool {
+    assigned_value(node, declaration, name).is_some()
+        || node
+            .descendants()
+            .iter()
+            .any(|descendant| assigned_value(descendant, declaration, name).is_some())
+}
+
+fn narrow_by_condition<'a>(
+    condition: &AST<Expression>,
+    holds: bool,
+    typ: Type,
+    declaration: &ASTAny,
+    name: &str,
+    ctx: InferTypeContext<'a>,
+) -> Type {
+    let subsumation_ctx = SubsumationContext::from(ctx);
+
+    match condition.downcast() {
+        Expression::UnaryOperation(UnaryOperation { op, subject }) => match op.downcast() {
+            UnaryOperator::Not => {
+                narrow_by_condition(&subject, !holds, typ, declaration, name, ctx)
+            }
+            _ => typ,
+        },
+        Expression::BinaryOperation(BinaryOperation { op, left, right }) => match op.downcast() {
+            BinaryOperator::And if holds => {
+                let typ = narrow_by_condition(&left, true, typ, declaration, name, ctx);
+                narrow_by_condition(&right, true, typ, declaration, name, ctx)
+            }
+            BinaryOperator::Or if !holds => {
+                let typ = narrow_by_condition(&left, false, typ, declaration, name, ctx);
+                narrow_by_condition(&right, false, typ, declaration, name, ctx)
+            }
+            op @ (BinaryOperator::Is | BinaryOperator::IsNot) => {
+                if !left.refers_to(declaration, name) {
+                    return typ;
+                }
+
+                match right.resolve_as_type(ctx.into()) {
+                    Some(target) if (op == BinaryOperator::Is) == holds => {
+                        typ.narrow_to(subsumation_ctx, &target)
+                    }
+                    Some(target) => typ.narrow_away(subsumation_ctx, &target),
+                    None => typ,
+                }
+            }
+            op @ (BinaryOperator::Equals | BinaryOperator::NotEquals) => {
+                let other = if left.refers_to(declaration, name) {
+                    right
+                } else if right.refers_to(declaration, name) {
+                    left
+                } else {
+                    return typ;
+                };
+
+                match other.downcast() {
+                    Expression::NullLiteral(_) if (op == BinaryOperator::Equals) == holds => {
+                        typ.narrow_to(subsumation_ctx, &Type::Null)
+                    }
+                    Expression::NullLiteral(_) => typ.non_null(),
+                    _ => typ,
+                }
+            }
+            _ => typ,
+        },
+        // truthiness
+        Expression::LocalIdentifier(_) if holds && condition.refers_to(declaration, name) => {
+            typ.non_null()
+        }
+        _ => typ,
+    }
+}
+
+fn narrow_by_match_arm<'a>(
+    subject: &AST<Expression>,
+    arm: &AST<MatchArm>,
+    typ: Type,
+    declaration: &ASTAny,
+    name: &str,
+    ctx: InferTypeContext<'a>,
+) -> Type {
+    if !subject.refers_to(declaration, name) {
+        return typ;
+    }
+
+    let pattern_types: Option<Vec<Type>> = arm
+        .downcast()
+        .patterns
+        .iter()
+        .map(|pattern| pattern.pattern_type(ctx))
+        .collect();
+
+    match pattern_types {
+        Some(pattern_types) => typ.narrow_to(ctx.into(), &Type::union(pattern_types)),
+        None => typ,
+    }
+}
+
+impl AST<MatchPattern> {
+    /// The type a value must have to match this pattern, if the pattern
+    /// constrains it at all
+    fn pattern_type<'a>(&self, ctx: InferTypeContext<'a>) -> Option<Type> {
+        match self.downcast() {
+            MatchPattern::ValuePattern(ValuePattern { value }) => Some(value.infer_type(ctx)),
+            MatchPattern::ArrayPattern(_) => Some(Type::Array {
+                element: std::rc::Rc::new(Type::Any),
+            }),
+            MatchPattern::DictionaryPattern(_) => Some(Type::Dictionary {
+                key: std::rc::Rc::new(Type::Any),
+                value: std::rc::Rc::new(Type::Any),
+            }),
+            MatchPattern::BindingPattern(_) | MatchPattern::WildcardPattern(_) => None,
+        }
+    }
+}
+
+#[test]
+fn narrowing() {
+    use super::{
+        check::{check_source, check_source_in, located},
+        rules::{Rule, RuleSeverity},
+    };
+    use crate::godot_project::GodotProject;
+    use std::collections::HashMap;
+
+    let code = "func f(n: Node, v):\n\tvar a: Node2D = n\n\tif n is Node2D:\n\t\tvar b: Node2D = n\n\t\tvar l: Control = n\n\telif n is Control:\n\t\tvar c: Control = n\n\t\tvar d: Node2D = n\n\twhile n is Node2D:\n\t\tvar e: Node2D = n\n\tif n is not Node2D:\n\t\treturn\n\tvar g: Node2D = n\n\tif v is int and v > 0:\n\t\tvar h: int = v\n\tvar i: int = v if v is int else 0\n\tmatch v:\n\t\t1, 2:\n\t\t\tvar j: int = v\n\t\t\"a\":\n\t\t\tvar k: int = v\n";
+
+    assert_eq!(
+        located(check_source(code)),
+        vec![
+            (
+                String::from("n"),
+                String::from(
+                    "Type Node2D! is not assignable to type Control\n  Type Node2D is not assignable to type Control"
+                )
+            ),
+            (
+                String::from("n"),
+                String::from(
+                    "Type Control! is not assignable to type Node2D\n  Type Control is not assignable to type Node2D"
+                )
+            ),
+            (
+                String::from("v"),
+                String::from("Type String (literal \"a\") is not assignable to type int")
+            ),
+        ]
+    );
+    // `a` is an implicit downcast, which is only reported when UnsafeCast
+    // is turned on
+    let project = GodotProject {
+        rule_severity: HashMap::from([(Rule::UnsafeCast, RuleSeverity::Warning)]),
+        ..GodotProject::for_test()
+    };
+    assert_eq!(
+        located(check_source_in(&project, "func f(n: Node):\n\tvar a: Node2D = n\n")),
+        vec![(
+            String::from("n"),
+            String::from(
+                "Type Node is implicitly cast down to type Node2D, which fails at runtime if the value isn't one"
+            )
+        )]
+    );
+}
@@ -0,0 +1,284 @@
+use std::rc::Rc;
+
+use super::{
+    ast::{BinaryOperator, UnaryOperator},
+    const_eval::{fold_binary, fold_unary, truthiness},
+    gd_type::Type,
+    infer_type::InferTypeContext,
+    resolve_type::resolve_api_type,
+};
+
+/// The type produced by `left <op> right`, or `None` if the operator isn't
+/// defined for those operand types. The result types come from the Variant
+/// operator table in the engine API; when both operands are literals, so is
+/// the result.
+pub fn binary_operation_type<'a>(
+    op: BinaryOperator,
+    left: &Type,
+    right: &Type,
+    ctx: InferTypeContext<'a>,
+) -> Option<Type> {
+    match op {
+        BinaryOperator::Is | BinaryOperator::IsNot => return Some(Type::Boolean(None)),
+        BinaryOperator::And | BinaryOperator::Or => {
+            let value = match (truthiness(left), truthiness(right)) {
+                (Some(left), Some(right)) if op == BinaryOperator::And => Some(left && right),
+                (Some(left), Some(right)) => Some(left || right),
+                _ => None,
+            };
+
+            return Some(Type::Boolean(value));
+        }
+        BinaryOperator::As => unreachable!("`as` takes a type, not a value"),
+        _ => {}
+    }
+
+    match (left, right) {
+        (Type::Poisoned, _) | (_, Type::Poisoned) => return Some(Type::Poisoned),
+        (Type::Any, _) | (_, Type::Any) | (Type::Unknown, _) | (_, Type::Unknown) => {
+            return Some(Type::Any)
+        }
+        _ => {}
+    }
+
+    // every combination of possible operand types has to be valid
+    if let Type::Union { members } = left {
+        return members
+            .iter()
+            .map(|member| binary_operation_type(op, member, right, ctx))
+            .collect::<Option<Vec<Type>>>()
+            .map(Type::union);
+    }
+    if let Type::Union { members } = right {
+        return members
+            .iter()
+            .map(|member| binary_operation_type(op, left, member, ctx))
+            .collect::<Option<Vec<Type>>>()
+            .map(Type::union);
+    }
+
+    let op_name: &'static str = op.into();
+    let left_name = operand_name(left)?;
+    let right_name = operand_name(right)?;
+
+    // checking for a property by name
+    if op == BinaryOperator::In && right_name == "Object" {
+        return Some(Type::Boolean(None));
+    }
+
+    // objects aren't in the table, but can always be compared by identity
+    // or looked up in a container
+    if left_name == "Object" {
+        return match op {
+            BinaryOperator::Equals | BinaryOperator::NotEquals => Some(Type::Boolean(None)),
+            BinaryOperator::In if matches!(right_name, "Array" | "Dictionary") => {
+                Some(Type::Boolean(None))
+            }
+            _ => None,
+        };
+    }
+
+    let builtin = ctx.godot_project.engine_api.builtin_class(left_name)?;
+    let operator = builtin.operators.iter().find(|operator| {
+        operator.name == op_name
+            && match operator.right_type.as_deref() {
+                Some("Variant") => true,
+                Some(right_type) => right_type == right_name,
+                None => false,
+            }
+    })?;
+
+    // a constant expression that fails to evaluate (like dividing by zero)
+    // is reported by the checker, here it just isn't a literal
+    match fold_binary(op, left, right) {
+        Ok(Some(folded)) => Some(folded),
+        _ => Some(result_type(&operator.return_type, left, right, ctx)),
+    }
+}
+
+/// The type produced by `<op> subject`, or `None` if the operator isn't
+/// defined for the operand type
+pub fn unary_operation_type<'a>(
+    op: UnaryOperator,
+    subject: &Type,
+    ctx: InferTypeContext<'a>,
+) -> Option<Type> {
+    match subject {
+        Type::Poisoned => return Some(Type::Poisoned),
+        Type::Any | Type::Unknown => return Some(Type::Any),
+        Type::Union { members } => {
+            return members
+                .iter()
+                .map(|member| unary_operation_type(op, member, ctx))
+                .collect::<Option<Vec<Type>>>()
+                .map(Type::union)
+        }
+        _ => {}
+    }
+
+    let op_name = match op {
+        UnaryOperator::BitwiseNot => "~",
+        UnaryOperator::Negative => "unary-",
+        UnaryOperator::Positive => "unary+",
+        // anything can be negated by its truthiness
+        UnaryOperator::Not => return Some(Type::Boolean(truthiness(subject).map(|value| !value))),
+    };
+
+    let builtin = ctx
+        .godot_project
+        .engine_api
+        .builtin_class(operand_name(subject)?)?;
+    let operator = builtin
+        .operators
+        .iter()
+        .find(|operator| operator.name == op_name && operator.right_type.is_none())?;
+
+    match fold_unary(op, subject) {
+        Ok(Some(folded)) => Some(folded),
+        _ => Some(result_type(&operator.return_type, subject, subject, ctx)),
+    }
+}
+
+/// The name an operand goes by in the operator table
+fn operand_name(typ: &Type) -> Option<&str> {
+    match typ {
+        Type::Class { name: _ } => Some("Object"),
+        Type::NonNull { inner } => operand_name(inner),
+        other => other.api_name(),
+    }
+}
+
+fn result_type<'a>(
+    return_type: &str,
+    left: &Type,
+    right: &Type,
+    ctx: InferTypeContext<'a>,
+) -> Type {
+    match resolve_api_type(return_type, ctx.into()) {
+        // concatenating typed arrays keeps the element type when both sides
+        // agree on it
+        Type::Array { element: _ } => match (left, right) {
+            (Type::Array { element }, Type::Array { element: other }) if element == other => {
+                Type::Array {
+                    element: element.clone(),
+                }
+            }
+            _ => Type::Array {
+                element: Rc::new(Type::Any),
+            },
+        },
+        other => other,
+    }
+}
+
+#[test]
+fn operator_table() {
+    let project = crate::godot_project::GodotProject::for_test();
+    let ctx = InferTypeContext {
+        godot_project: &project,
+    };
+    let binary = |op, left: Type, right: Type| binary_operation_type(op, &left, &right, ctx);
+
+    assert_eq!(
+        binary(BinaryOperator::Star, Type::Vector2, Type::Float(Some(2.0))),
+        Some(Type::Vector2)
+    );
+    assert_eq!(
+        binary(BinaryOperator::Slash, Type::Int(Some(7)), Type::Int(None)),
+        Some(Type::Int(None))
+    );
+    assert_eq!(
+        binary(
+            BinaryOperator::Slash,
+            Type::Int(Some(7)),
+            Type::Int(Some(2))
+        ),
+        Some(Type::Int(Some(3)))
+    );
+    assert_eq!(
+        binary(
+            BinaryOperator::Slash,
+            Type::Int(Some(7)),
+            Type::Int(Some(0))
+        ),
+        Some(Type::Int(None))
+    );
+    assert_eq!(
+        binary(BinaryOperator::Plus, Type::Int(None), Type::Float(None)),
+        Some(Type::Float(None))
+    );
+    assert_eq!(
+        binary(
+            BinaryOperator::Percent,
+            Type::String(None),
+            Type::ExactArray {
+                members: vec![Type::Int(None)]
+            }
+        ),
+        Some(Type::String(None))
+    );
+    assert_eq!(
+        binary(
+            BinaryOperator::Plus,
+            Type::Array {
+                element: Rc::new(Type::Int(None))
+            },
+            Type::Array {
+                element: Rc::new(Type::Int(None))
+            }
+        ),
+        Some(Type::Array {
+            element: Rc::new(Type::Int(None))
+        })
+    );
+    assert_eq!(
+        binary(BinaryOperator::Plus, Type::Int(None), Type::String(None)),
+        None
+    );
+    assert_eq!(
+        binary(
+            BinaryOperator::Plus,
+            Type::union([Type::Int(None), Type::Null]),
+            Type::Int(None)
+        ),
+        None
+    );
+    assert_eq!(
+        binary(BinaryOperator::Equals, Type::Vector2, Type::Null),
+        Some(Type::Boolean(None))
+    );
+
+    assert_eq!(
+        unary_operation_type(UnaryOperator::Negative, &Type::Vector3, ctx),
+        Some(Type::Vector3)
+    );
+    assert_eq!(
+        unary_operation_type(UnaryOperator::BitwiseNot, &Type::Float(None), ctx),
+        None
+    );
+}
+
+#[test]
+fn parsed_operators() {
+    use super::check::{check_source, located};
+
+    let code = "func f(v: Vector2, n: int):\n\tvar a: int = \"a\" - 1\n\tvar b: Vector2 = v * 2.0\n\tvar c: int = v * 2\n\tvar d := -\"s\"\n\tvar e: bool = n < 3 and not n == 2\n\tvar g: String = \"%d\" % n\n\tvar h: int = 1 << 2 | n\n\tvar i: bool = n in [1, 2]\n";
+
+    assert_eq!(
+        located(check_source(code)),
+        vec![
+            (
+                String::from("\"a\" - 1"),
+                String::from("Invalid operands 'String' and 'int' for operator '-'")
+            ),
+            (
+                String::from("v * 2"),
+                String::from("Type Vector2 is not assignable to type int")
+            ),
+            (
+                String::from("-\"s\""),
+                String::from("Invalid operand 'String' for unary operator '-'")
+            ),
+        ]
+    );
+}
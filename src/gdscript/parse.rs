@@ -1,18 +1,21 @@
+use std::str::FromStr;
+
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while, take_while1},
-    combinator::{map, opt},
-    multi::{many0, many1, separated_list0},
-    sequence::{preceded, terminated, tuple},
+    combinator::{map, not, opt, verify},
+    multi::{many0, many1, separated_list0, separated_list1},
+    sequence::{delimited, preceded, terminated, tuple},
 };
 
 use crate::utils::{
     errors::ParseError,
+    number_literal,
     slice::{Slicable, Slice},
-    ParseResult, RawParseError, RawParseErrorDetails,
+    string_literal, ParseResult, RawParseError, RawParseErrorDetails,
 };
 
-use super::ast::*;
+use super::{ast::*, scope::build_scopes};
 
 macro_rules! seq {
     ($( $s:expr ),* $(,)?) => {
@@ -52,14 +55,14 @@ macro_rules! make_node_tuple {
     };
 }
 
-pub fn parse_script(module_id: ModuleID, code: Slice) -> Result<GDScript, ParseError> {
+pub fn parse_script(module_id: ModuleID, code: Slice) -> Result<AST<GDScript>, ParseError> {
     let res = terminated(
-        many0(preceded(whitespace_and_comments, parse_declaration)),
+        many0(preceded(whitespace_and_comments, parse_declaration(0))),
         whitespace_and_comments,
     )(code.clone());
 
     match res {
-        Ok((i, declarations)) => {
+        Ok((i, mut declarations)) => {
             // if i.len() > 0 {
             //     Err(ParseError {
             //         index: Some(i.slice.start),
@@ -67,7 +70,11 @@ pub fn parse_script(module_id: ModuleID, code: Slice) -> Result<GDScript, ParseE
             //         message: "Failed to parse entire input".to_owned(),
             //     })
             // } else {
-            Ok(GDScript { declarations })
+            let script = make_node!(GDScript, code, declarations);
+
+            build_scopes(&script);
+
+            Ok(script)
             // }
         }
         Err(error) => Err(match error {
@@ -96,21 +103,55 @@ pub fn parse_script(module_id: ModuleID, code: Slice) -> Result<GDScript, ParseE
     }
 }
 
-fn parse_declaration(i: Slice) -> ParseResult<AST<Declaration>> {
-    alt((
-        map(parse_extends, AST::recast::<Declaration>),
-        map(parse_class_name, AST::recast::<Declaration>),
-        map(parse_val_declaration, AST::recast::<Declaration>),
-        map(parse_func(0), AST::recast::<Declaration>),
-        map(parse_annotation, AST::recast::<Declaration>),
-    ))(i)
+fn parse_declaration(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<Declaration>> {
+    move |i: Slice| -> ParseResult<AST<Declaration>> {
+        alt((
+            map(parse_extends, AST::recast::<Declaration>),
+            map(parse_class_name, AST::recast::<Declaration>),
+            map(parse_signal, AST::recast::<Declaration>),
+            map(parse_val_declaration, AST::recast::<Declaration>),
+            map(parse_func(indentation), AST::recast::<Declaration>),
+            map(parse_class(indentation), AST::recast::<Declaration>),
+            map(parse_annotation, AST::recast::<Declaration>),
+        ))(i)
+    }
+}
+
+fn parse_class(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<ClassDeclaration>> {
+    move |i: Slice| -> ParseResult<AST<ClassDeclaration>> {
+        map(
+            tuple((
+                word("class"),
+                preceded(whitespace_and_comments, plain_identifier),
+                preceded(whitespace_and_comments, tag(":")),
+                many1(preceded(
+                    whitespace_and_comments,
+                    preceded(
+                        parse_indentation(indentation + 1),
+                        parse_declaration(indentation + 1),
+                    ),
+                )),
+            )),
+            |(keyword, mut name, _, mut declarations)| {
+                make_node!(
+                    ClassDeclaration,
+                    keyword.spanning(declarations.iter().last().unwrap()),
+                    name,
+                    declarations
+                )
+            },
+        )(i)
+    }
 }
 
 fn parse_extends(i: Slice) -> ParseResult<AST<ExtendsDeclaration>> {
     map(
         tuple((
             tag("extends"),
-            preceded(whitespace_and_comments1, plain_identifier),
+            alt((
+                preceded(whitespace_and_comments1, plain_identifier),
+                preceded(whitespace_and_comments, script_path),
+            )),
         )),
         |(start, mut extends_class)| {
             make_node!(
@@ -122,6 +163,19 @@ fn parse_extends(i: Slice) -> ParseResult<AST<ExtendsDeclaration>> {
     )(i)
 }
 
+/// A script extended by its path, like `extends "res://enemy.gd"`. The
+/// identifier holds the path without its quotes.
+fn script_path(i: Slice) -> ParseResult<AST<PlainIdentifier>> {
+    map(
+        delimited(
+            tag("\""),
+            take_while1(|ch: char| ch != '"' && ch != '\n'),
+            tag("\""),
+        ),
+        |path: Slice| PlainIdentifier { name: path.clone() }.as_ast(path),
+    )(i)
+}
+
 fn parse_class_name(i: Slice) -> ParseResult<AST<ClassNameDeclaration>> {
     map(
         tuple((
@@ -152,6 +206,32 @@ fn parse_annotation(i: Slice) -> ParseResult<AST<Annotation>> {
     )(i)
 }
 
+fn parse_signal(i: Slice) -> ParseResult<AST<SignalDeclaration>> {
+    map(
+        tuple((
+            tag("signal"),
+            preceded(whitespace_and_comments1, plain_identifier),
+            opt(tuple((
+                preceded(whitespace_and_comments, tag("(")),
+                separated_list0(
+                    preceded(whitespace_and_comments, tag(",")),
+                    preceded(whitespace_and_comments, parse_func_arg),
+                ),
+                preceded(whitespace_and_comments, tag(")")),
+            ))),
+        )),
+        |(keyword, mut name, args)| {
+            let src = match &args {
+                Some((_, _, close)) => keyword.spanning(close),
+                None => keyword.spanning(&name),
+            };
+            let mut args = args.map(|(_, args, _)| args).unwrap_or_default();
+
+            make_node!(SignalDeclaration, src, name, args)
+        },
+    )(i)
+}
+
 fn parse_func(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<FuncDeclaration>> {
     move |i: Slice| -> ParseResult<AST<FuncDeclaration>> {
         map(
@@ -159,15 +239,20 @@ fn parse_func(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<FuncDecla
                 tag("func"),
                 preceded(whitespace_and_comments, plain_identifier),
                 preceded(whitespace_and_comments, tag("(")),
-                // preceded(whitespace_and_comments, args),
+                separated_list0(
+                    preceded(whitespace_and_comments, tag(",")),
+                    preceded(whitespace_and_comments, parse_func_arg),
+                ),
                 preceded(whitespace_and_comments, tag(")")),
+                opt(preceded(
+                    whitespace_and_comments,
+                    preceded(tag("->"), preceded(whitespace_and_comments, parse_type)),
+                )),
                 preceded(whitespace_and_comments, tag(":")),
                 parse_block(indentation + 1),
             )),
-            |(keyword, mut name, _, _, _, mut body)| {
+            |(keyword, mut name, _, mut args, _, mut return_type, _, mut body)| {
                 let mut is_static = false; // TODO
-                let mut args = vec![]; // TODO
-                let mut return_type = None; // TODO
 
                 make_node!(
                     FuncDeclaration,
@@ -183,6 +268,25 @@ fn parse_func(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<FuncDecla
     }
 }
 
+fn parse_func_arg(i: Slice) -> ParseResult<AST<FuncArg>> {
+    map(
+        tuple((
+            plain_identifier,
+            opt(preceded(whitespace_and_comments, parse_type_declaration)),
+            opt(preceded(whitespace_and_comments, parse_initial_value)),
+        )),
+        |(mut name, mut declared_type, mut default_value)| {
+            let src = match (&declared_type, &default_value) {
+                (_, Some(default_value)) => name.slice().clone().spanning(default_value),
+                (Some(declared_type), None) => name.slice().clone().spanning(declared_type),
+                (None, None) => name.slice().clone(),
+            };
+
+            make_node!(FuncArg, src, name, declared_type, default_value)
+        },
+    )(i)
+}
+
 fn parse_block(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<Block>> {
     move |i: Slice| -> ParseResult<AST<Block>> {
         map(
@@ -205,23 +309,329 @@ fn parse_block(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<Block>>
 }
 
 fn parse_statement(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<Statement>> {
-    |i: Slice| -> ParseResult<AST<Statement>> {
+    move |i: Slice| -> ParseResult<AST<Statement>> {
         alt((
             map(tag("pass"), |src| Pass.as_ast(src).recast::<Statement>()),
             map(tag("break"), |src| Break.as_ast(src).recast::<Statement>()),
+            map(tag("continue"), |src| {
+                Continue.as_ast(src).recast::<Statement>()
+            }),
+            map(parse_return, AST::recast::<Statement>),
+            map(parse_for_loop(indentation), AST::recast::<Statement>),
+            map(parse_while_loop(indentation), AST::recast::<Statement>),
+            map(parse_if_else(indentation), AST::recast::<Statement>),
+            map(parse_match(indentation), AST::recast::<Statement>),
+            map(parse_val_declaration, AST::recast::<Statement>),
+            map(parse_assignment, AST::recast::<Statement>),
         ))(i)
     }
 }
 
+fn parse_assignment(i: Slice) -> ParseResult<AST<AssignmentStatement>> {
+    map(
+        tuple((
+            parse_expression,
+            preceded(whitespace_and_comments, parse_assignment_operator),
+            preceded(whitespace_and_comments, parse_expression),
+        )),
+        |(mut target, mut operator, mut value)| {
+            let src = target.slice().clone().spanning(&value);
+
+            make_node!(AssignmentStatement, src, target, value, operator)
+        },
+    )(i)
+}
+
+/// `=`, or a compound assignment like `+=` along with the operator it applies
+fn parse_assignment_operator(i: Slice) -> ParseResult<Option<AST<BinaryOperator>>> {
+    alt((
+        map(
+            terminated(
+                alt((
+                    tag("**"),
+                    tag("<<"),
+                    tag(">>"),
+                    tag("+"),
+                    tag("-"),
+                    tag("*"),
+                    tag("/"),
+                    tag("%"),
+                    tag("&"),
+                    tag("|"),
+                    tag("^"),
+                )),
+                tag("="),
+            ),
+            |op: Slice| Some(BinaryOperator::from_str(op.as_str()).unwrap().as_ast(op)),
+        ),
+        map(terminated(tag("="), not(tag("="))), |_| None),
+    ))(i)
+}
+
+fn parse_for_loop(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<ForLoop>> {
+    move |i: Slice| -> ParseResult<AST<ForLoop>> {
+        map(
+            tuple((
+                tag("for"),
+                preceded(whitespace_and_comments1, plain_identifier),
+                opt(preceded(whitespace_and_comments, parse_type_declaration)),
+                preceded(whitespace_and_comments1, tag("in")),
+                preceded(whitespace_and_comments1, parse_expression),
+                preceded(whitespace_and_comments, tag(":")),
+                parse_block(indentation + 1),
+            )),
+            |(keyword, mut item_name, mut item_type, _, mut iteree, _, mut body)| {
+                make_node!(
+                    ForLoop,
+                    keyword.spanning(&body),
+                    item_name,
+                    item_type,
+                    iteree,
+                    body
+                )
+            },
+        )(i)
+    }
+}
+
+fn parse_while_loop(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<WhileLoop>> {
+    move |i: Slice| -> ParseResult<AST<WhileLoop>> {
+        map(
+            tuple((
+                word("while"),
+                preceded(whitespace_and_comments, parse_expression),
+                preceded(whitespace_and_comments, tag(":")),
+                parse_block(indentation + 1),
+            )),
+            |(keyword, mut condition, _, mut body)| {
+                make_node!(WhileLoop, keyword.spanning(&body), condition, body)
+            },
+        )(i)
+    }
+}
+
+fn parse_if_else(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<IfElseStatement>> {
+    move |i: Slice| -> ParseResult<AST<IfElseStatement>> {
+        let branch = |keyword_str: &'static str| {
+            tuple((
+                word(keyword_str),
+                preceded(whitespace_and_comments, parse_expression),
+                preceded(whitespace_and_comments, tag(":")),
+                parse_block(indentation + 1),
+            ))
+        };
+
+        map(
+            tuple((
+                branch("if"),
+                many0(preceded(
+                    whitespace_and_comments,
+                    preceded(parse_indentation(indentation), branch("elif")),
+                )),
+                opt(preceded(
+                    whitespace_and_comments,
+                    preceded(
+                        parse_indentation(indentation),
+                        tuple((
+                            word("else"),
+                            preceded(whitespace_and_comments, tag(":")),
+                            parse_block(indentation + 1),
+                        )),
+                    ),
+                )),
+            )),
+            |((start, condition, _, body), elifs, default_outcome)| {
+                let mut conditions = std::iter::once((condition, body))
+                    .chain(
+                        elifs
+                            .into_iter()
+                            .map(|(_, condition, _, body)| (condition, body)),
+                    )
+                    .collect::<Vec<_>>();
+                let mut default_outcome = default_outcome.map(|(_, _, body)| body);
+                let src = match &default_outcome {
+                    Some(default_outcome) => start.spanning(default_outcome),
+                    None => start.spanning(&conditions.last().unwrap().1),
+                };
+
+                make_node!(IfElseStatement, src, conditions, default_outcome)
+            },
+        )(i)
+    }
+}
+
+fn parse_match(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<MatchStatement>> {
+    move |i: Slice| -> ParseResult<AST<MatchStatement>> {
+        map(
+            tuple((
+                word("match"),
+                preceded(whitespace_and_comments, parse_expression),
+                preceded(whitespace_and_comments, tag(":")),
+                many1(preceded(
+                    whitespace_and_comments,
+                    preceded(
+                        parse_indentation(indentation + 1),
+                        parse_match_arm(indentation + 1),
+                    ),
+                )),
+            )),
+            |(keyword, mut subject, _, mut arms)| {
+                make_node!(
+                    MatchStatement,
+                    keyword.spanning(arms.last().unwrap()),
+                    subject,
+                    arms
+                )
+            },
+        )(i)
+    }
+}
+
+fn parse_match_arm(indentation: usize) -> impl Fn(Slice) -> ParseResult<AST<MatchArm>> {
+    move |i: Slice| -> ParseResult<AST<MatchArm>> {
+        map(
+            tuple((
+                separated_list1(
+                    preceded(whitespace_and_comments, tag(",")),
+                    preceded(whitespace_and_comments, parse_match_pattern),
+                ),
+                opt(preceded(
+                    preceded(whitespace_and_comments, word("when")),
+                    preceded(whitespace_and_comments, parse_expression),
+                )),
+                preceded(whitespace_and_comments, tag(":")),
+                parse_block(indentation + 1),
+            )),
+            |(mut patterns, mut guard, _, mut body)| {
+                make_node!(
+                    MatchArm,
+                    patterns[0].slice().clone().spanning(&body),
+                    patterns,
+                    guard,
+                    body
+                )
+            },
+        )(i)
+    }
+}
+
+fn parse_match_pattern(i: Slice) -> ParseResult<AST<MatchPattern>> {
+    alt((
+        map(
+            tuple((
+                word("var"),
+                preceded(whitespace_and_comments1, plain_identifier),
+            )),
+            |(keyword, mut name)| {
+                make_node!(BindingPattern, keyword.spanning(&name), name).recast::<MatchPattern>()
+            },
+        ),
+        map(word("_"), |src| {
+            WildcardPattern.as_ast(src).recast::<MatchPattern>()
+        }),
+        map(
+            tuple((
+                tag("["),
+                separated_list0(
+                    preceded(whitespace_and_comments, tag(",")),
+                    preceded(
+                        whitespace_and_comments,
+                        alt((map(tag(".."), |_| None), map(parse_match_pattern, Some))),
+                    ),
+                ),
+                preceded(whitespace_and_comments, tag("]")),
+            )),
+            |(open, members, close)| {
+                let mut open_ended = members.iter().any(Option::is_none);
+                let mut members = members.into_iter().flatten().collect::<Vec<_>>();
+
+                make_node!(ArrayPattern, open.spanning(&close), members, open_ended)
+                    .recast::<MatchPattern>()
+            },
+        ),
+        map(
+            tuple((
+                tag("{"),
+                separated_list0(
+                    preceded(whitespace_and_comments, tag(",")),
+                    preceded(
+                        whitespace_and_comments,
+                        alt((
+                            map(tag(".."), |_| None),
+                            map(
+                                tuple((
+                                    parse_expression,
+                                    opt(preceded(
+                                        preceded(whitespace_and_comments, tag(":")),
+                                        preceded(whitespace_and_comments, parse_match_pattern),
+                                    )),
+                                )),
+                                Some,
+                            ),
+                        )),
+                    ),
+                ),
+                preceded(whitespace_and_comments, tag("}")),
+            )),
+            |(open, entries, close)| {
+                let mut open_ended = entries.iter().any(Option::is_none);
+                let mut entries = entries.into_iter().flatten().collect::<Vec<_>>();
+
+                make_node!(
+                    DictionaryPattern,
+                    open.spanning(&close),
+                    entries,
+                    open_ended
+                )
+                .recast::<MatchPattern>()
+            },
+        ),
+        map(parse_expression, |mut value| {
+            make_node!(ValuePattern, value.slice().clone(), value).recast::<MatchPattern>()
+        }),
+    ))(i)
+}
+
+fn parse_return(i: Slice) -> ParseResult<AST<Return>> {
+    map(
+        tuple((
+            tag("return"),
+            opt(preceded(
+                take_while(|c: char| c == ' ' || c == '\t'),
+                parse_expression,
+            )),
+        )),
+        |(keyword, mut expr)| {
+            let src = match &expr {
+                Some(expr) => keyword.spanning(expr),
+                None => keyword,
+            };
+
+            make_node!(Return, src, expr)
+        },
+    )(i)
+}
+
 fn parse_val_declaration(i: Slice) -> ParseResult<AST<ValueDeclaration>> {
     map(
         tuple((
             alt((tag("var"), tag("const"))),
             preceded(whitespace_and_comments, plain_identifier),
-            opt(preceded(whitespace_and_comments, parse_type_declaration)),
-            opt(preceded(whitespace_and_comments, parse_initial_value)),
+            alt((
+                map(
+                    preceded(whitespace_and_comments, parse_inferred_value),
+                    |value| (None, true, Some(value)),
+                ),
+                map(
+                    tuple((
+                        opt(preceded(whitespace_and_comments, parse_type_declaration)),
+                        opt(preceded(whitespace_and_comments, parse_initial_value)),
+                    )),
+                    |(declared_type, value)| (declared_type, false, value),
+                ),
+            )),
         )),
-        |(keyword, mut name, mut declared_type, mut value)| {
+        |(keyword, mut name, (mut declared_type, mut is_inferred, mut value))| {
             let mut is_const = keyword.as_str() == "const";
 
             make_node!(
@@ -237,6 +647,7 @@ fn parse_val_declaration(i: Slice) -> ParseResult<AST<ValueDeclaration>> {
                 is_const,
                 name,
                 declared_type,
+                is_inferred,
                 value
             )
         },
@@ -260,15 +671,408 @@ fn parse_initial_value(i: Slice) -> ParseResult<AST<Expression>> {
     )(i)
 }
 
+fn parse_inferred_value(i: Slice) -> ParseResult<AST<Expression>> {
+    map(
+        tuple((
+            tag(":="),
+            preceded(whitespace_and_comments, parse_expression),
+        )),
+        |(_, expression)| expression,
+    )(i)
+}
+
+// Each of the functions below parses one level of operator precedence,
+// from loosest to tightest, with operands at the next level down
+
 fn parse_expression(i: Slice) -> ParseResult<AST<Expression>> {
+    let (mut i, mut expression) = parse_ternary(i)?;
+
+    while let Ok((rest, (op, target))) = tuple((
+        preceded(
+            inline_whitespace,
+            map(word("as"), |src| BinaryOperator::As.as_ast(src)),
+        ),
+        preceded(whitespace_and_comments, parse_postfix_expression),
+    ))(i.clone())
+    {
+        expression = binary_operation(expression, op, target);
+        i = rest;
+    }
+
+    Ok((i, expression))
+}
+
+fn parse_ternary(i: Slice) -> ParseResult<AST<Expression>> {
+    let (i, if_true) = parse_or(i)?;
+
+    match tuple((
+        preceded(inline_whitespace, word("if")),
+        preceded(whitespace_and_comments, parse_or),
+        preceded(whitespace_and_comments, word("else")),
+        preceded(whitespace_and_comments, parse_ternary),
+    ))(i.clone())
+    {
+        Ok((rest, (_, mut condition, _, mut if_false))) => {
+            let mut if_true = if_true;
+            let src = if_true.slice().clone().spanning(&if_false);
+
+            Ok((
+                rest,
+                make_node!(TernaryExpression, src, condition, if_true, if_false)
+                    .recast::<Expression>(),
+            ))
+        }
+        Err(_) => Ok((i, if_true)),
+    }
+}
+
+fn parse_or(i: Slice) -> ParseResult<AST<Expression>> {
+    parse_binary_level(
+        &[("or", BinaryOperator::Or), ("||", BinaryOperator::Or)],
+        parse_and,
+    )(i)
+}
+
+fn parse_and(i: Slice) -> ParseResult<AST<Expression>> {
+    parse_binary_level(
+        &[("and", BinaryOperator::And), ("&&", BinaryOperator::And)],
+        parse_not,
+    )(i)
+}
+
+fn parse_not(i: Slice) -> ParseResult<AST<Expression>> {
+    alt((
+        map(
+            tuple((
+                alt((word("not"), operator("!"))),
+                preceded(whitespace_and_comments, parse_not),
+            )),
+            |(op, subject)| unary_operation(UnaryOperator::Not.as_ast(op), subject),
+        ),
+        parse_in,
+    ))(i)
+}
+
+fn parse_in(i: Slice) -> ParseResult<AST<Expression>> {
+    let (mut i, mut expression) = parse_comparison(i)?;
+
+    while let Ok((rest, (negation, op, container))) = tuple((
+        preceded(
+            inline_whitespace,
+            opt(terminated(word("not"), whitespace_and_comments1)),
+        ),
+        word("in"),
+        preceded(whitespace_and_comments, parse_comparison),
+    ))(i.clone())
+    {
+        let operation = binary_operation(expression, BinaryOperator::In.as_ast(op), container);
+
+        // `a not in b` is `not (a in b)`
+        expression = match negation {
+            Some(negation) => unary_operation(UnaryOperator::Not.as_ast(negation), operation),
+            None => operation,
+        };
+        i = rest;
+    }
+
+    Ok((i, expression))
+}
+
+fn parse_comparison(i: Slice) -> ParseResult<AST<Expression>> {
+    parse_binary_level(
+        &[
+            ("==", BinaryOperator::Equals),
+            ("!=", BinaryOperator::NotEquals),
+            ("<=", BinaryOperator::LessEqual),
+            (">=", BinaryOperator::GreaterEqual),
+            ("<", BinaryOperator::Less),
+            (">", BinaryOperator::Greater),
+        ],
+        parse_bitwise_or,
+    )(i)
+}
+
+fn parse_bitwise_or(i: Slice) -> ParseResult<AST<Expression>> {
+    parse_binary_level(&[("|", BinaryOperator::BitwiseOr)], parse_bitwise_xor)(i)
+}
+
+fn parse_bitwise_xor(i: Slice) -> ParseResult<AST<Expression>> {
+    parse_binary_level(&[("^", BinaryOperator::BitwiseXor)], parse_bitwise_and)(i)
+}
+
+fn parse_bitwise_and(i: Slice) -> ParseResult<AST<Expression>> {
+    parse_binary_level(&[("&", BinaryOperator::BitwiseAnd)], parse_shift)(i)
+}
+
+fn parse_shift(i: Slice) -> ParseResult<AST<Expression>> {
+    parse_binary_level(
+        &[
+            ("<<", BinaryOperator::ShiftLeft),
+            (">>", BinaryOperator::ShiftRight),
+        ],
+        parse_sum,
+    )(i)
+}
+
+fn parse_sum(i: Slice) -> ParseResult<AST<Expression>> {
+    parse_binary_level(
+        &[("+", BinaryOperator::Plus), ("-", BinaryOperator::Minus)],
+        parse_product,
+    )(i)
+}
+
+fn parse_product(i: Slice) -> ParseResult<AST<Expression>> {
+    parse_binary_level(
+        &[
+            ("*", BinaryOperator::Star),
+            ("/", BinaryOperator::Slash),
+            ("%", BinaryOperator::Percent),
+        ],
+        parse_sign,
+    )(i)
+}
+
+fn parse_sign(i: Slice) -> ParseResult<AST<Expression>> {
     alt((
+        map(
+            tuple((
+                alt((
+                    map(operator("-"), |src| UnaryOperator::Negative.as_ast(src)),
+                    map(operator("+"), |src| UnaryOperator::Positive.as_ast(src)),
+                    map(operator("~"), |src| UnaryOperator::BitwiseNot.as_ast(src)),
+                )),
+                preceded(whitespace_and_comments, parse_sign),
+            )),
+            |(op, subject)| unary_operation(op, subject),
+        ),
+        parse_power,
+    ))(i)
+}
+
+fn parse_power(i: Slice) -> ParseResult<AST<Expression>> {
+    parse_binary_level(&[("**", BinaryOperator::DoubleStar)], parse_type_test)(i)
+}
+
+fn parse_type_test(i: Slice) -> ParseResult<AST<Expression>> {
+    let (mut i, mut expression) = parse_postfix_expression(i)?;
+
+    while let Ok((rest, (is, negation, typ))) = tuple((
+        preceded(inline_whitespace, word("is")),
+        opt(preceded(whitespace_and_comments1, word("not"))),
+        preceded(whitespace_and_comments, parse_postfix_expression),
+    ))(i.clone())
+    {
+        let op = match negation {
+            Some(negation) => BinaryOperator::IsNot.as_ast(is.spanning(&negation)),
+            None => BinaryOperator::Is.as_ast(is),
+        };
+
+        expression = binary_operation(expression, op, typ);
+        i = rest;
+    }
+
+    Ok((i, expression))
+}
+
+/// A level of left-associative binary operators, given as their spellings
+/// along with what they mean. Spellings that start with another one at the
+/// same level have to come first.
+fn parse_binary_level(
+    operators: &'static [(&'static str, BinaryOperator)],
+    operand: fn(Slice) -> ParseResult<AST<Expression>>,
+) -> impl Fn(Slice) -> ParseResult<AST<Expression>> {
+    move |i: Slice| -> ParseResult<AST<Expression>> {
+        let (mut i, mut expression) = operand(i)?;
+
+        // operators have to be on the same line as their left operand, so
+        // that a statement starting with `-` or `not` isn't mistaken for the
+        // end of the one before it
+        while let Ok((rest, (op, right))) = tuple((
+            preceded(inline_whitespace, binary_operator(operators)),
+            preceded(whitespace_and_comments, operand),
+        ))(i.clone())
+        {
+            expression = binary_operation(expression, op, right);
+            i = rest;
+        }
+
+        Ok((i, expression))
+    }
+}
+
+fn binary_operation(
+    mut left: AST<Expression>,
+    mut op: AST<BinaryOperator>,
+    mut right: AST<Expression>,
+) -> AST<Expression> {
+    let src = left.slice().clone().spanning(&right);
+
+    make_node!(BinaryOperation, src, op, left, right).recast::<Expression>()
+}
+
+fn unary_operation(mut op: AST<UnaryOperator>, mut subject: AST<Expression>) -> AST<Expression> {
+    let src = op.slice().clone().spanning(&subject);
+
+    make_node!(UnaryOperation, src, op, subject).recast::<Expression>()
+}
+
+/// Whichever of `operators` comes next
+fn binary_operator(
+    operators: &'static [(&'static str, BinaryOperator)],
+) -> impl Fn(Slice) -> ParseResult<AST<BinaryOperator>> {
+    move |i: Slice| -> ParseResult<AST<BinaryOperator>> {
+        operators
+            .iter()
+            .find_map(|(spelling, op)| {
+                operator(spelling)(i.clone())
+                    .ok()
+                    .map(|(rest, src)| (rest, op.as_ast(src)))
+            })
+            .ok_or_else(|| {
+                nom::Err::Error(RawParseError {
+                    src: i.clone(),
+                    details: RawParseErrorDetails::Kind(nom::error::ErrorKind::Tag),
+                })
+            })
+    }
+}
+
+/// An operator spelled with symbols, as long as it isn't the start of a
+/// longer operator like `**`, `<=` or `+=`
+fn operator(spelling: &'static str) -> impl Fn(Slice) -> ParseResult<Slice> {
+    move |i: Slice| -> ParseResult<Slice> {
+        let (rest, src) = tag(spelling)(i)?;
+        let next = rest.as_str();
+
+        if (next.starts_with('=') && !spelling.ends_with('='))
+            || (spelling.len() == 1 && next.starts_with(spelling))
+        {
+            return Err(nom::Err::Error(RawParseError {
+                src: rest,
+                details: RawParseErrorDetails::Kind(nom::error::ErrorKind::Tag),
+            }));
+        }
+
+        Ok((rest, src))
+    }
+}
+
+/// A word like `if` or `and`, as long as it isn't the start of a longer name
+fn word(spelling: &'static str) -> impl Fn(Slice) -> ParseResult<Slice> {
+    move |i: Slice| -> ParseResult<Slice> {
+        terminated(
+            tag(spelling),
+            not(take_while1(|ch: char| ch.is_alphanumeric() || ch == '_')),
+        )(i)
+    }
+}
+
+fn parse_postfix_expression(i: Slice) -> ParseResult<AST<Expression>> {
+    let (mut i, mut expression) = parse_primary_expression(i)?;
+
+    // calls, property accesses and subscripts chain onto whatever comes
+    // before them
+    while let Ok((rest, postfix)) = parse_postfix(i.clone()) {
+        let src = expression.slice().clone();
+
+        expression = match postfix {
+            Postfix::Call(mut args, close) => {
+                let mut function = expression;
+
+                make_node!(FunctionCall, src.spanning(&close), function, args)
+                    .recast::<Expression>()
+            }
+            Postfix::Property(mut property) => {
+                let mut subject = expression;
+
+                make_node!(PropertyAccess, src.spanning(&property), subject, property)
+                    .recast::<Expression>()
+            }
+            Postfix::Subscript(mut index, close) => {
+                let mut subject = expression;
+
+                make_node!(Subscript, src.spanning(&close), subject, index).recast::<Expression>()
+            }
+        };
+        i = rest;
+    }
+
+    Ok((i, expression))
+}
+
+/// Something that can follow an expression to make a bigger one
+enum Postfix {
+    Call(Vec<AST<Expression>>, Slice),
+    Property(AST<PlainIdentifier>),
+    Subscript(AST<Expression>, Slice),
+}
+
+fn parse_postfix(i: Slice) -> ParseResult<Postfix> {
+    alt((
+        map(
+            tuple((
+                tag("("),
+                preceded(
+                    whitespace_and_comments,
+                    separated_list0(
+                        preceded(whitespace_and_comments, tag(",")),
+                        preceded(whitespace_and_comments, parse_expression),
+                    ),
+                ),
+                preceded(whitespace_and_comments, tag(")")),
+            )),
+            |(_, args, close)| Postfix::Call(args, close),
+        ),
+        map(preceded(tag("."), plain_identifier), Postfix::Property),
+        map(
+            tuple((
+                tag("["),
+                preceded(whitespace_and_comments, parse_expression),
+                preceded(whitespace_and_comments, tag("]")),
+            )),
+            |(_, index, close)| Postfix::Subscript(index, close),
+        ),
+    ))(i)
+}
+
+fn parse_primary_expression(i: Slice) -> ParseResult<AST<Expression>> {
+    alt((
+        delimited(
+            tag("("),
+            preceded(whitespace_and_comments, parse_expression),
+            preceded(whitespace_and_comments, tag(")")),
+        ),
         map(parse_array_literal, AST::recast::<Expression>),
+        map(parse_dictionary_literal, AST::recast::<Expression>),
+        map(string_literal, |src| {
+            StringLiteral {
+                value: src.clone().slice_range(1, Some(src.len() - 1)),
+                multiline: false,
+            }
+            .as_ast(src)
+            .recast::<Expression>()
+        }),
+        map(number_literal, |src| {
+            if src.as_str().contains('.') {
+                FloatLiteral {
+                    value_raw: src.clone(),
+                }
+                .as_ast(src)
+                .recast::<Expression>()
+            } else {
+                IntLiteral {
+                    value_raw: src.clone(),
+                }
+                .as_ast(src)
+                .recast::<Expression>()
+            }
+        }),
         map(tag("true"), |src| {
             BooleanLiteral { value: true }
                 .as_ast(src)
                 .recast::<Expression>()
         }),
-        map(tag("true"), |src| {
+        map(tag("false"), |src| {
             BooleanLiteral { value: false }
                 .as_ast(src)
                 .recast::<Expression>()
@@ -276,9 +1080,53 @@ fn parse_expression(i: Slice) -> ParseResult<AST<Expression>> {
         map(tag("null"), |src| {
             NullLiteral.as_ast(src).recast::<Expression>()
         }),
+        map(parse_local_identifier, AST::recast::<Expression>),
     ))(i)
 }
 
+/// Words that can't be used as names
+const KEYWORDS: &[&str] = &[
+    "and",
+    "as",
+    "await",
+    "break",
+    "class",
+    "class_name",
+    "const",
+    "continue",
+    "elif",
+    "else",
+    "enum",
+    "extends",
+    "for",
+    "func",
+    "if",
+    "in",
+    "is",
+    "match",
+    "not",
+    "or",
+    "pass",
+    "return",
+    "signal",
+    "static",
+    "var",
+    "while",
+];
+
+fn parse_local_identifier(i: Slice) -> ParseResult<AST<LocalIdentifier>> {
+    map(
+        verify(
+            take_while1(|ch: char| ch.is_alphanumeric() || ch == '_'),
+            |name: &Slice| {
+                !KEYWORDS.contains(&name.as_str())
+                    && !name.as_str().starts_with(|ch: char| ch.is_ascii_digit())
+            },
+        ),
+        |name: Slice| LocalIdentifier { name: name.clone() }.as_ast(name),
+    )(i)
+}
+
 fn parse_array_literal(i: Slice) -> ParseResult<AST<ArrayLiteral>> {
     map(
         tuple((
@@ -296,16 +1144,111 @@ fn parse_array_literal(i: Slice) -> ParseResult<AST<ArrayLiteral>> {
     )(i)
 }
 
+fn parse_dictionary_literal(i: Slice) -> ParseResult<AST<DictionaryLiteral>> {
+    map(
+        tuple((
+            tag("{"),
+            preceded(
+                whitespace_and_comments,
+                separated_list0(
+                    preceded(whitespace_and_comments, tag(",")),
+                    map(
+                        tuple((
+                            preceded(whitespace_and_comments, parse_expression),
+                            preceded(whitespace_and_comments, tag(":")),
+                            preceded(whitespace_and_comments, parse_expression),
+                        )),
+                        |(key, _, value)| (key, value),
+                    ),
+                ),
+            ),
+            preceded(whitespace_and_comments, tag("}")),
+        )),
+        |(open, mut entries, close)| make_node!(DictionaryLiteral, open.spanning(&close), entries),
+    )(i)
+}
+
 fn parse_type(i: Slice) -> ParseResult<AST<TypeExpression>> {
     alt((
-        map(tag("int"), |src| {
-            IntType.as_ast(src).recast::<TypeExpression>()
-        }),
-        map(tag("float"), |src| {
-            FloatType.as_ast(src).recast::<TypeExpression>()
-        }),
-        map(tag("String"), |src| {
-            StringType.as_ast(src).recast::<TypeExpression>()
+        map(
+            tuple((
+                tag("Array"),
+                preceded(whitespace_and_comments, tag("[")),
+                preceded(whitespace_and_comments, parse_type),
+                preceded(whitespace_and_comments, tag("]")),
+            )),
+            |(start, _, mut element, end)| {
+                make_node!(ArrayType, start.spanning(&end), element).recast::<TypeExpression>()
+            },
+        ),
+        map(
+            tuple((
+                tag("Dictionary"),
+                preceded(whitespace_and_comments, tag("[")),
+                preceded(whitespace_and_comments, parse_type),
+                preceded(whitespace_and_comments, tag(",")),
+                preceded(whitespace_and_comments, parse_type),
+                preceded(whitespace_and_comments, tag("]")),
+            )),
+            |(start, _, mut key, _, mut value, end)| {
+                make_node!(DictionaryType, start.spanning(&end), key, value)
+                    .recast::<TypeExpression>()
+            },
+        ),
+        map(plain_identifier, |mut name| {
+            let src = name.slice().clone();
+
+            // matching whole identifiers keeps `int` from matching the start
+            // of a class named `Interactable`
+            match name.downcast().name.as_str() {
+                "void" => VoidType.as_ast(src).recast::<TypeExpression>(),
+                "int" => IntType.as_ast(src).recast::<TypeExpression>(),
+                "float" => FloatType.as_ast(src).recast::<TypeExpression>(),
+                "bool" => BooleanType.as_ast(src).recast::<TypeExpression>(),
+                "String" => StringType.as_ast(src).recast::<TypeExpression>(),
+                "StringName" => StringNameType.as_ast(src).recast::<TypeExpression>(),
+                "Vector2" => Vector2Type.as_ast(src).recast::<TypeExpression>(),
+                "Vector2i" => Vector2iType.as_ast(src).recast::<TypeExpression>(),
+                "Rect2" => Rect2Type.as_ast(src).recast::<TypeExpression>(),
+                "Rect2i" => Rect2iType.as_ast(src).recast::<TypeExpression>(),
+                "Vector3" => Vector3Type.as_ast(src).recast::<TypeExpression>(),
+                "Vector3i" => Vector3iType.as_ast(src).recast::<TypeExpression>(),
+                "Vector4" => Vector4Type.as_ast(src).recast::<TypeExpression>(),
+                "Vector4i" => Vector4iType.as_ast(src).recast::<TypeExpression>(),
+                "Transform2D" => Transform2DType.as_ast(src).recast::<TypeExpression>(),
+                "Plane" => PlaneType.as_ast(src).recast::<TypeExpression>(),
+                "Quaternion" => QuaternionType.as_ast(src).recast::<TypeExpression>(),
+                "AABB" => AABBType.as_ast(src).recast::<TypeExpression>(),
+                "Basis" => BasisType.as_ast(src).recast::<TypeExpression>(),
+                "Transform3D" => Transform3DType.as_ast(src).recast::<TypeExpression>(),
+                "Projection" => ProjectionType.as_ast(src).recast::<TypeExpression>(),
+                "Color" => ColorType.as_ast(src).recast::<TypeExpression>(),
+                "NodePath" => NodePathType.as_ast(src).recast::<TypeExpression>(),
+                "RID" => RIDType.as_ast(src).recast::<TypeExpression>(),
+                "Object" => ObjectType.as_ast(src).recast::<TypeExpression>(),
+                "Callable" => CallableType.as_ast(src).recast::<TypeExpression>(),
+                "Signal" => SignalType.as_ast(src).recast::<TypeExpression>(),
+                "Array" => UntypedArrayType.as_ast(src).recast::<TypeExpression>(),
+                "Dictionary" => UntypedDictionaryType.as_ast(src).recast::<TypeExpression>(),
+                "PackedByteArray" => PackedByteArrayType.as_ast(src).recast::<TypeExpression>(),
+                "PackedInt32Array" => PackedInt32ArrayType.as_ast(src).recast::<TypeExpression>(),
+                "PackedInt64Array" => PackedInt64ArrayType.as_ast(src).recast::<TypeExpression>(),
+                "PackedFloat32Array" => PackedFloat32ArrayType
+                    .as_ast(src)
+                    .recast::<TypeExpression>(),
+                "PackedFloat64Array" => PackedFloat64ArrayType
+                    .as_ast(src)
+                    .recast::<TypeExpression>(),
+                "PackedStringArray" => PackedStringArrayType.as_ast(src).recast::<TypeExpression>(),
+                "PackedVector2Array" => PackedVector2ArrayType
+                    .as_ast(src)
+                    .recast::<TypeExpression>(),
+                "PackedVector3Array" => PackedVector3ArrayType
+                    .as_ast(src)
+                    .recast::<TypeExpression>(),
+                "PackedColorArray" => PackedColorArrayType.as_ast(src).recast::<TypeExpression>(),
+                _ => make_node!(NamedType, src, name).recast::<TypeExpression>(),
+            }
         }),
     ))(i)
 }
@@ -330,6 +1273,11 @@ fn whitespace_and_comments(i: Slice) -> ParseResult<()> {
     )(i)
 }
 
+/// Spaces and tabs, without going on to the next line
+fn inline_whitespace(i: Slice) -> ParseResult<()> {
+    map(take_while(|c: char| c == ' ' || c == '\t'), |_| ())(i)
+}
+
 fn whitespace_and_comments1(i: Slice) -> ParseResult<()> {
     map(
         many1(alt((
@@ -378,3 +1326,140 @@ fn get_indentation_str_spaces(indentation: usize) -> &'static str {
         _ => unimplemented!(),
     }
 }
+
+#[test]
+fn inferred_declarations() {
+    let code = "var a := true\nvar b: bool = true\nvar c = true\nvar d := 10.0";
+    let script = parse_script(
+        ModuleID(std::rc::Rc::new(String::from("test.gd"))),
+        Slice::new(std::rc::Rc::new(code.to_owned())),
+    )
+    .unwrap();
+
+    let flags: Vec<(bool, bool)> = script
+        .downcast()
+        .declarations
+        .iter()
+        .map(|declaration| match declaration.downcast() {
+            Declaration::ValueDeclaration(ValueDeclaration {
+                is_const: _,
+                name: _,
+                declared_type,
+                is_inferred,
+                value: _,
+            }) => (declared_type.is_some(), is_inferred),
+            _ => panic!("expected a value declaration"),
+        })
+        .collect();
+
+    assert_eq!(
+        flags,
+        vec![(false, true), (true, false), (false, false), (false, true)]
+    );
+}
+
+#[test]
+fn func_args() {
+    let code = "func f(a: int, b = true, c) -> bool:\n\tpass";
+    let script = parse_script(
+        ModuleID(std::rc::Rc::new(String::from("test.gd"))),
+        Slice::new(std::rc::Rc::new(code.to_owned())),
+    )
+    .unwrap();
+
+    let func = script.downcast().declarations[0]
+        .clone()
+        .upcast()
+        .try_recast::<FuncDeclaration>()
+        .unwrap()
+        .downcast();
+    let args: Vec<(String, bool, bool)> = func
+        .args
+        .iter()
+        .map(|arg| {
+            let FuncArg {
+                name,
+                declared_type,
+                default_value,
+            } = arg.downcast();
+
+            (
+                name.downcast().name.as_str().to_owned(),
+                declared_type.is_some(),
+                default_value.is_some(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        args,
+        vec![
+            (String::from("a"), true, false),
+            (String::from("b"), false, true),
+            (String::from("c"), false, false)
+        ]
+    );
+    assert!(func.return_type.is_some());
+}
+
+#[test]
+fn operator_precedence() {
+    fn render(expression: &AST<Expression>) -> String {
+        match expression.downcast() {
+            Expression::BinaryOperation(BinaryOperation { op, left, right }) => format!(
+                "({} {} {})",
+                render(&left),
+                <&str>::from(op.downcast()),
+                render(&right)
+            ),
+            Expression::UnaryOperation(UnaryOperation { op, subject }) => {
+                format!("({} {})", <&str>::from(op.downcast()), render(&subject))
+            }
+            Expression::TernaryExpression(TernaryExpression {
+                condition,
+                if_true,
+                if_false,
+            }) => format!(
+                "({} if {} else {})",
+                render(&if_true),
+                render(&condition),
+                render(&if_false)
+            ),
+            _ => expression.slice().as_str().to_owned(),
+        }
+    }
+
+    let code = "var a = not x or y and z == 1 + 2 * -3 ** 2\nvar b = p if q else r as int\nvar c = n is not Node and m not in [1]\nvar d = (1 + 2) * 3 - 4 - 5 << 1 & 7 | 8 ^ 9\nvar e = order != 1 || android";
+    let script = parse_script(
+        ModuleID(std::rc::Rc::new(String::from("test.gd"))),
+        Slice::new(std::rc::Rc::new(code.to_owned())),
+    )
+    .unwrap();
+
+    let rendered: Vec<String> = script
+        .downcast()
+        .declarations
+        .iter()
+        .map(|declaration| match declaration.downcast() {
+            Declaration::ValueDeclaration(ValueDeclaration {
+                is_const: _,
+                name: _,
+                declared_type: _,
+                is_inferred: _,
+                value: Some(value),
+            }) => render(&value),
+            _ => panic!("expected a value declaration with a value"),
+        })
+        .collect();
+
+    assert_eq!(
+        rendered,
+        vec![
+            "((! x) or (y and (z == (1 + (2 * (- (3 ** 2)))))))",
+            "((p if q else r) as int)",
+            "((n is not Node) and (! (m in [1])))",
+            "(((((((1 + 2) * 3) - 4) - 5) << 1) & 7) | (8 ^ 9))",
+            "((order != 1) or android)",
+        ]
+    );
+}
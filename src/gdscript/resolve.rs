@@ -1,74 +1,72 @@
-use super::ast::*;
+use crate::utils::slice::Slicable;
+
+use super::{ast::*, scope::visible_to_inner_classes};
+
 impl<TKind> AST<TKind>
 where
     TKind: Clone + TryFrom<Any>,
     Any: From<TKind>,
 {
+    /// The declaration `symbol` refers to when used at this node, looked up
+    /// through the scopes enclosing it from the innermost out
     pub fn resolve_symbol(&self, symbol: &str) -> Option<ASTAny> {
-        match self.parent().as_ref().map(|p| p.details()) {
-            Some(Any::GDScript(GDScript { declarations })) => {
-                for decl in declarations {
-                    match &decl.downcast() {
-                        Declaration::ValueDeclaration(ValueDeclaration {
-                            is_const,
-                            name,
-                            declared_type,
-                            value,
-                        }) => {
-                            if name.downcast().name.as_str() == symbol {
-                                return Some(decl.clone().upcast());
-                            }
-                        }
-                        Declaration::FuncDeclaration(FuncDeclaration {
-                            is_static,
-                            name,
-                            args,
-                            return_type,
-                            body,
-                        }) => {
-                            if name.downcast().name.as_str() == symbol {
-                                return Some(decl.clone().upcast());
-                            }
-                        }
-                        Declaration::EnumDeclaration(_) => todo!(),
-                        Declaration::ClassDeclaration(_) => todo!(),
+        let position = self.slice().start;
+        let mut current = self.parent();
+        let mut in_inner_class = false;
 
-                        Declaration::ExtendsDeclaration(_) => {}
-                        Declaration::ClassNameDeclaration(_) => {}
-                        Declaration::Annotation(_) => {}
-                    }
-                }
+        while let Some(node) = current {
+            if let Some(found) = node
+                .scope()
+                .and_then(|scope| scope.lookup(symbol, position))
+                .filter(|found| !in_inner_class || visible_to_inner_classes(found))
+            {
+                return Some(found.clone());
             }
-            Some(Any::Block(Block { statements })) => {
-                for stmt in statements {
-                    match &stmt.downcast() {
-                        Statement::ValueDeclaration(ValueDeclaration {
-                            is_const,
-                            name,
-                            declared_type,
-                            value,
-                        }) => {
-                            if name.downcast().name.as_str() == symbol {
-                                return Some(stmt.clone().upcast());
-                            }
-                        }
-                        Statement::ForLoop(_) => todo!(),
 
-                        Statement::AssignmentStatement(_) => {}
-                        Statement::MatchStatement(_) => {}
-                        Statement::WhileLoop(_) => {}
-                        Statement::IfElseStatement(_) => {}
-                        Statement::Pass(_) => {}
-                        Statement::Break(_) => {}
-                        Statement::Return(_) => {}
-                    }
-                }
+            in_inner_class |= matches!(node.details(), Any::ClassDeclaration(_));
+            current = node.parent();
+        }
+
+        None
+    }
+
+    /// Every name declared in the scopes enclosing this node and visible
+    /// from it
+    pub fn names_in_scope(&self) -> Vec<String> {
+        let position = self.slice().start;
+        let mut names = Vec::new();
+        let mut current = self.parent();
+        let mut in_inner_class = false;
+
+        while let Some(node) = current {
+            if let Some(scope) = node.scope() {
+                let mut scope_names: Vec<String> = scope
+                    .visible_names(position)
+                    .filter(|name| {
+                        !in_inner_class
+                            || scope
+                                .lookup(name, position)
+                                .is_some_and(visible_to_inner_classes)
+                    })
+                    .map(String::from)
+                    .collect();
+
+                // scopes are unordered, but lists of names shouldn't be
+                scope_names.sort();
+                names.append(&mut scope_names);
             }
-            _ => {}
+
+            in_inner_class |= matches!(node.details(), Any::ClassDeclaration(_));
+            current = node.parent();
         }
 
-        self.parent()
-            .map(|parent| parent.resolve_symbol(symbol))
-            .flatten()
+        names
+    }
+}
+
+impl AST<GDScript> {
+    /// The top-level declaration of `symbol` in this script
+    pub fn declaration(&self, symbol: &str) -> Option<ASTAny> {
+        self.scope()?.lookup(symbol, 0).cloned()
     }
 }
@@ -29,35 +29,81 @@ impl AST<TypeExpression> {
     pub fn resolve_type<'a>(&self, ctx: ResolveContext<'a>) -> Type {
         match self.downcast() {
             TypeExpression::NullType(_) => Type::Null,
+            TypeExpression::VoidType(_) => Type::Void,
             TypeExpression::BooleanType(_) => Type::Boolean(None),
             TypeExpression::IntType(_) => Type::Int(None),
             TypeExpression::FloatType(_) => Type::Float(None),
             TypeExpression::StringType(_) => Type::String(None),
-            TypeExpression::StringNameType(_) => todo!(),
+            TypeExpression::StringNameType(_) => Type::StringName,
             TypeExpression::Vector2Type(_) => Type::Vector2,
             TypeExpression::Vector2iType(_) => Type::Vector2i,
+            TypeExpression::Rect2Type(_) => Type::Rect2,
+            TypeExpression::Rect2iType(_) => Type::Rect2i,
             TypeExpression::Vector3Type(_) => Type::Vector3,
             TypeExpression::Vector3iType(_) => Type::Vector3i,
+            TypeExpression::Vector4Type(_) => Type::Vector4,
+            TypeExpression::Vector4iType(_) => Type::Vector4i,
             TypeExpression::Transform2DType(_) => Type::Transform2D,
             TypeExpression::PlaneType(_) => Type::Plane,
+            TypeExpression::QuaternionType(_) => Type::Quaternion,
             TypeExpression::AABBType(_) => Type::AABB,
             TypeExpression::BasisType(_) => Type::Basis,
             TypeExpression::Transform3DType(_) => Type::Transform3D,
+            TypeExpression::ProjectionType(_) => Type::Projection,
             TypeExpression::ColorType(_) => Type::Color,
             TypeExpression::NodePathType(_) => Type::NodePath,
             TypeExpression::RIDType(_) => Type::RID,
             TypeExpression::ObjectType(_) => Type::Object,
-            TypeExpression::NamedType(NamedType { name }) => todo!(),
-            TypeExpression::NonNullType(NonNullType { inner }) => Type::NonNull {
-                inner: Rc::new(inner.resolve_type(ctx)),
+            TypeExpression::CallableType(_) => Type::Callable,
+            TypeExpression::SignalType(_) => Type::Signal,
+            TypeExpression::UntypedArrayType(_) => Type::Array {
+                element: Rc::new(Type::Any),
             },
-            TypeExpression::ArrayType(ArrayType { element }) => Type::Array {
-                element: Rc::new(element.resolve_type(ctx)),
+            TypeExpression::UntypedDictionaryType(_) => Type::Dictionary {
+                key: Rc::new(Type::Any),
+                value: Rc::new(Type::Any),
             },
-            TypeExpression::DictionaryType(DictionaryType { key, value }) => Type::Dictionary {
-                key: Rc::new(key.resolve_type(ctx)),
-                value: Rc::new(value.resolve_type(ctx)),
+            TypeExpression::PackedByteArrayType(_) => Type::PackedByteArray,
+            TypeExpression::PackedInt32ArrayType(_) => Type::PackedInt32Array,
+            TypeExpression::PackedInt64ArrayType(_) => Type::PackedInt64Array,
+            TypeExpression::PackedFloat32ArrayType(_) => Type::PackedFloat32Array,
+            TypeExpression::PackedFloat64ArrayType(_) => Type::PackedFloat64Array,
+            TypeExpression::PackedStringArrayType(_) => Type::PackedStringArray,
+            TypeExpression::PackedVector2ArrayType(_) => Type::PackedVector2Array,
+            TypeExpression::PackedVector3ArrayType(_) => Type::PackedVector3Array,
+            TypeExpression::PackedColorArrayType(_) => Type::PackedColorArray,
+            TypeExpression::NamedType(NamedType { name }) => {
+                let name = name.downcast().name;
+
+                // a constant holding a preloaded script names that script's
+                // class
+                self.resolve_symbol(name.as_str())
+                    .and_then(|declaration| preloaded_class(&declaration, ctx))
+                    .or_else(|| resolve_type_name(name.as_str(), ctx))
+                    .unwrap_or(Type::Poisoned)
+            }
+            // a type built out of an undeclared one is just as poisoned
+            TypeExpression::NonNullType(NonNullType { inner }) => match inner.resolve_type(ctx) {
+                Type::Poisoned => Type::Poisoned,
+                inner => Type::NonNull {
+                    inner: Rc::new(inner),
+                },
+            },
+            TypeExpression::ArrayType(ArrayType { element }) => match element.resolve_type(ctx) {
+                Type::Poisoned => Type::Poisoned,
+                element => Type::Array {
+                    element: Rc::new(element),
+                },
             },
+            TypeExpression::DictionaryType(DictionaryType { key, value }) => {
+                match (key.resolve_type(ctx), value.resolve_type(ctx)) {
+                    (Type::Poisoned, _) | (_, Type::Poisoned) => Type::Poisoned,
+                    (key, value) => Type::Dictionary {
+                        key: Rc::new(key),
+                        value: Rc::new(value),
+                    },
+                }
+            }
             TypeExpression::ExactDictionaryType(ExactDictionaryType { entries }) => {
                 Type::ExactDictionary {
                     entries: entries
@@ -69,3 +115,164 @@ impl AST<TypeExpression> {
         }
     }
 }
+
+impl AST<Expression> {
+    /// Resolve an expression that names a type, like the right-hand side of
+    /// `x is Foo`
+    pub fn resolve_as_type<'a>(&self, ctx: ResolveContext<'a>) -> Option<Type> {
+        match self.downcast() {
+            Expression::LocalIdentifier(LocalIdentifier { name }) => self
+                .resolve_symbol(name.as_str())
+                .and_then(|declaration| preloaded_class(&declaration, ctx))
+                .or_else(|| resolve_type_name(name.as_str(), ctx)),
+            Expression::FunctionCall(_) => self.preloaded_script_class(ctx),
+            Expression::NullLiteral(_) => Some(Type::Null),
+            _ => None,
+        }
+    }
+
+    /// If this expression is `preload()`ing one of the project's scripts,
+    /// the class that script declares
+    fn preloaded_script_class<'a>(&self, ctx: ResolveContext<'a>) -> Option<Type> {
+        let FunctionCall { function, args } = self.try_downcast::<FunctionCall>()?;
+
+        match (function.downcast(), args.as_slice()) {
+            (Expression::LocalIdentifier(LocalIdentifier { name }), [path])
+                if name.as_str() == "preload" =>
+            {
+                let StringLiteral {
+                    value,
+                    multiline: _,
+                } = path.try_downcast::<StringLiteral>()?;
+                let godot_project = ctx.godot_project;
+                let script = godot_project.script_at(value.as_str())?;
+
+                Some(Type::Class {
+                    name: godot_project.script_class_name(script)?,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// If `declaration` is a constant holding a preloaded script (directly or
+/// through other constants), the class that script declares
+fn preloaded_class<'a>(declaration: &ASTAny, ctx: ResolveContext<'a>) -> Option<Type> {
+    let mut visited: Vec<ASTAny> = Vec::new();
+    let mut current = declaration.clone();
+
+    loop {
+        // constants that refer to each other in a cycle hold nothing
+        if visited.iter().any(|other| other.ptr_eq(&current)) {
+            return None;
+        }
+
+        let value = match current.details() {
+            Any::ValueDeclaration(ValueDeclaration {
+                is_const: true,
+                name: _,
+                declared_type: _,
+                is_inferred: _,
+                value: Some(value),
+            }) => value.clone(),
+            _ => return None,
+        };
+
+        if let Some(class) = value.preloaded_script_class(ctx) {
+            return Some(class);
+        }
+
+        let LocalIdentifier { name } = value.try_downcast::<LocalIdentifier>()?;
+        let next = value.resolve_symbol(name.as_str())?;
+
+        visited.push(current);
+        current = next;
+    }
+}
+
+pub fn resolve_type_name<'a>(name: &str, ctx: ResolveContext<'a>) -> Option<Type> {
+    match name {
+        "bool" => Some(Type::Boolean(None)),
+        "int" => Some(Type::Int(None)),
+        "float" => Some(Type::Float(None)),
+        "String" => Some(Type::String(None)),
+        "StringName" => Some(Type::StringName),
+        "Vector2" => Some(Type::Vector2),
+        "Vector2i" => Some(Type::Vector2i),
+        "Rect2" => Some(Type::Rect2),
+        "Rect2i" => Some(Type::Rect2i),
+        "Vector3" => Some(Type::Vector3),
+        "Vector3i" => Some(Type::Vector3i),
+        "Vector4" => Some(Type::Vector4),
+        "Vector4i" => Some(Type::Vector4i),
+        "Transform2D" => Some(Type::Transform2D),
+        "Plane" => Some(Type::Plane),
+        "Quaternion" => Some(Type::Quaternion),
+        "AABB" => Some(Type::AABB),
+        "Basis" => Some(Type::Basis),
+        "Transform3D" => Some(Type::Transform3D),
+        "Projection" => Some(Type::Projection),
+        "Color" => Some(Type::Color),
+        "NodePath" => Some(Type::NodePath),
+        "RID" => Some(Type::RID),
+        "Object" => Some(Type::Object),
+        "Callable" => Some(Type::Callable),
+        "Signal" => Some(Type::Signal),
+        "PackedByteArray" => Some(Type::PackedByteArray),
+        "PackedInt32Array" => Some(Type::PackedInt32Array),
+        "PackedInt64Array" => Some(Type::PackedInt64Array),
+        "PackedFloat32Array" => Some(Type::PackedFloat32Array),
+        "PackedFloat64Array" => Some(Type::PackedFloat64Array),
+        "PackedStringArray" => Some(Type::PackedStringArray),
+        "PackedVector2Array" => Some(Type::PackedVector2Array),
+        "PackedVector3Array" => Some(Type::PackedVector3Array),
+        "PackedColorArray" => Some(Type::PackedColorArray),
+        "Array" => Some(Type::Array {
+            element: Rc::new(Type::Any),
+        }),
+        "Dictionary" => Some(Type::Dictionary {
+            key: Rc::new(Type::Any),
+            value: Rc::new(Type::Any),
+        }),
+        _ => {
+            let godot_project = ctx.godot_project;
+
+            // scripts referred to by path still go by their class_name
+            if let Some(script) = godot_project.script_class(name) {
+                return godot_project
+                    .script_class_name(script)
+                    .map(|name| Type::Class { name });
+            }
+
+            if godot_project.class_exists(name) {
+                Some(Type::Class {
+                    name: Rc::new(name.to_owned()),
+                })
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Resolve a type name as written in the engine API, which has a few forms
+/// of its own on top of the ones scripts use
+pub fn resolve_api_type<'a>(name: &str, ctx: ResolveContext<'a>) -> Type {
+    if let Some(element) = name.strip_prefix("typedarray::") {
+        return Type::Array {
+            element: Rc::new(resolve_api_type(element, ctx)),
+        };
+    }
+
+    if name.starts_with("enum::") || name.starts_with("bitfield::") {
+        return Type::Int(None);
+    }
+
+    match name {
+        "Variant" => Type::Any,
+        "Nil" => Type::Null,
+        // engine types we don't model more precisely yet
+        _ => resolve_type_name(name, ctx).unwrap_or(Type::Any),
+    }
+}
@@ -0,0 +1,115 @@
+use super::ast::*;
+
+impl AST<FuncDeclaration> {
+    /// Every `return` statement that returns from this function, leaving
+    /// out the ones inside lambdas
+    pub fn returns(&self) -> Vec<AST<Return>> {
+        self.clone()
+            .upcast()
+            .descendants()
+            .into_iter()
+            .filter_map(|node| node.try_recast::<Return>())
+            .filter(|ret| {
+                enclosing_function(&ret.clone().upcast()).is_some_and(|func| self.ptr_eq(&func))
+            })
+            .collect()
+    }
+}
+
+/// The function or lambda that a `return` at `node` would return from
+pub fn enclosing_function(node: &ASTAny) -> Option<ASTAny> {
+    node.find_parent(|parent| matches!(parent.details(), Any::FuncDeclaration(_) | Any::Lambda(_)))
+}
+
+/// Whether every path through `block` ends in a `return`
+pub fn always_returns(block: &AST<Block>) -> bool {
+    block
+        .downcast()
+        .statements
+        .iter()
+        .any(|statement| match statement.downcast() {
+            Statement::Return(_) => true,
+            Statement::IfElseStatement(IfElseStatement {
+                conditions,
+                default_outcome: Some(default_outcome),
+            }) => {
+                conditions.iter().all(|(_, body)| always_returns(body))
+                    && always_returns(&default_outcome)
+            }
+            // a match only covers every path if some arm matches anything
+            Statement::MatchStatement(MatchStatement { subject: _, arms }) => {
+                arms.iter().all(|arm| always_returns(&arm.downcast().body))
+                    && arms.iter().any(|arm| {
+                        let MatchArm {
+                            patterns,
+                            guard,
+                            body: _,
+                        } = arm.downcast();
+
+                        guard.is_none()
+                            && patterns.iter().any(|pattern| {
+                                matches!(
+                                    pattern.downcast(),
+                                    MatchPattern::WildcardPattern(_)
+                                        | MatchPattern::BindingPattern(_)
+                                )
+                            })
+                    })
+            }
+            _ => false,
+        })
+}
+
+#[test]
+fn function_returns() {
+    use super::{
+        check::{check_source, located},
+        gd_type::Type,
+        parse::parse_script,
+    };
+    use crate::utils::slice::Slice;
+    use std::rc::Rc;
+
+    let code = "func a() -> void:\n\treturn\nfunc b():\n\treturn true\nfunc c():\n\tpass\nfunc d() -> bool:\n\tpass\nfunc e() -> void:\n\treturn null\n";
+    let module_id = ModuleID(Rc::new(String::from("test.gd")));
+    let script = parse_script(module_id.clone(), Slice::new(Rc::new(code.to_owned()))).unwrap();
+    let project = crate::godot_project::GodotProject::for_test();
+    let ctx = super::check::CheckContext {
+        module_id: &module_id,
+        godot_project: &project,
+    };
+
+    let return_types: Vec<Type> = script
+        .downcast()
+        .declarations
+        .iter()
+        .filter_map(|declaration| declaration.clone().upcast().try_recast::<FuncDeclaration>())
+        .map(|func| func.return_type(ctx.into()))
+        .collect();
+    assert_eq!(
+        return_types,
+        vec![
+            Type::Void,
+            Type::Boolean(None),
+            Type::Void,
+            Type::Boolean(None),
+            Type::Void
+        ]
+    );
+
+    // only d, which may fall off its end, and e, which returns a value
+    // from a void function, are reported
+    assert_eq!(
+        located(check_source(code)),
+        vec![
+            (
+                String::from("d"),
+                String::from("Not all code paths in 'd' return a value")
+            ),
+            (
+                String::from("null"),
+                String::from("Cannot return a value from a void function")
+            ),
+        ]
+    );
+}
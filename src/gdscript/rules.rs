@@ -1,10 +1,66 @@
-use strum_macros::EnumString;
+use std::{collections::HashMap, str::FromStr};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumString)]
+use strum_macros::{EnumString, IntoStaticStr};
+
+use toml_edit::{ImDocument, Item, Value};
+
+use crate::{
+    gdproject_metadata::ast::{EntryValue, GDProjectMetadata},
+    utils::{errors::GDError, slice::Slice},
+};
+
+use super::ast::ModuleID;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumString, IntoStaticStr)]
 pub enum Rule {
     RequireTypeAnnotations,
     AssignmentType,
+    UnsafeCast,
     StrictAny,
+    DuplicateDeclaration,
+    ShadowedVariable,
+    ShadowedVariableBaseClass,
+    ShadowedGlobalIdentifier,
+    UndeclaredIdentifier,
+    ArgumentCount,
+    ReturnType,
+    InvalidInheritance,
+    InvalidOperation,
+    UnknownMember,
+    InvalidAssignment,
+    InferredNull,
+    NonConstantPreload,
+    IntegerOverflow,
+    DivisionByZero,
+    GlobalClassRegistry,
+}
+
+impl Rule {
+    /// How a rule is reported when the project doesn't configure it
+    pub fn default_severity(self) -> RuleSeverity {
+        match self {
+            Rule::RequireTypeAnnotations => RuleSeverity::Off,
+            Rule::AssignmentType => RuleSeverity::Error,
+            Rule::UnsafeCast => RuleSeverity::Off,
+            Rule::StrictAny => RuleSeverity::Off,
+            Rule::DuplicateDeclaration => RuleSeverity::Error,
+            Rule::ShadowedVariable => RuleSeverity::Warning,
+            Rule::ShadowedVariableBaseClass => RuleSeverity::Warning,
+            Rule::ShadowedGlobalIdentifier => RuleSeverity::Warning,
+            Rule::UndeclaredIdentifier => RuleSeverity::Error,
+            Rule::ArgumentCount => RuleSeverity::Error,
+            Rule::ReturnType => RuleSeverity::Error,
+            Rule::InvalidInheritance => RuleSeverity::Error,
+            Rule::InvalidOperation => RuleSeverity::Error,
+            Rule::UnknownMember => RuleSeverity::Error,
+            Rule::InvalidAssignment => RuleSeverity::Error,
+            Rule::InferredNull => RuleSeverity::Error,
+            Rule::NonConstantPreload => RuleSeverity::Error,
+            Rule::IntegerOverflow => RuleSeverity::Error,
+            Rule::DivisionByZero => RuleSeverity::Error,
+            Rule::GlobalClassRegistry => RuleSeverity::Error,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumString)]
@@ -18,3 +74,444 @@ pub enum RuleSeverity {
     #[strum(serialize = "off")]
     Off,
 }
+
+/// Which declarations `RequireTypeAnnotations` wants annotated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TypeAnnotationOptions {
+    pub variables: bool,
+    pub parameters: bool,
+    pub return_types: bool,
+    pub for_variables: bool,
+
+    /// Don't require annotations on functions and members whose names
+    /// start with `_`
+    pub allow_private: bool,
+
+    /// Don't require annotations on local variables that have an initial
+    /// value to infer their type from
+    pub allow_inferred_locals: bool,
+}
+
+impl Default for TypeAnnotationOptions {
+    fn default() -> Self {
+        Self {
+            variables: true,
+            parameters: true,
+            return_types: true,
+            for_variables: true,
+            allow_private: false,
+            allow_inferred_locals: false,
+        }
+    }
+}
+
+/// Options that fine-tune what individual rules report
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct RuleOptions {
+    pub type_annotations: TypeAnnotationOptions,
+}
+
+impl RuleOptions {
+    /// Set one of `rule`'s options, returning whether the rule has it
+    pub fn set(&mut self, rule: Rule, option: &str, value: bool) -> bool {
+        let field = match (rule, option) {
+            (Rule::RequireTypeAnnotations, "variables") => &mut self.type_annotations.variables,
+            (Rule::RequireTypeAnnotations, "parameters") => &mut self.type_annotations.parameters,
+            (Rule::RequireTypeAnnotations, "return_types") => {
+                &mut self.type_annotations.return_types
+            }
+            (Rule::RequireTypeAnnotations, "for_variables") => {
+                &mut self.type_annotations.for_variables
+            }
+            (Rule::RequireTypeAnnotations, "allow_private") => {
+                &mut self.type_annotations.allow_private
+            }
+            (Rule::RequireTypeAnnotations, "allow_inferred_locals") => {
+                &mut self.type_annotations.allow_inferred_locals
+            }
+            _ => return false,
+        };
+
+        *field = value;
+        true
+    }
+}
+
+/// A value a rule or one of its options is set to, in either config format
+enum ConfigValue {
+    Boolean(bool),
+    String(String),
+    Other,
+}
+
+impl ConfigValue {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            ConfigValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// One setting from a config file: the key to report problems against, the
+/// rule it configures, the option it sets (if it doesn't set the rule's
+/// severity) and the value it's set to
+type ConfigEntry = (Slice, Slice, Option<Slice>, ConfigValue);
+
+/// Apply the rule configuration in project.godot on top of `severities` and
+/// `options`, from entries like `rules/AssignmentType="warning"` and
+/// `rules/RequireTypeAnnotations/allow_private=true` under `[gdcheck]`.
+/// Unknown rules, options and values are reported against `config_file`
+/// and otherwise ignored.
+pub fn read_rule_config<F: FnMut(GDError)>(
+    config: &GDProjectMetadata,
+    config_file: &ModuleID,
+    severities: &mut HashMap<Rule, RuleSeverity>,
+    options: &mut RuleOptions,
+    report_error: &mut F,
+) {
+    let mut entries: Vec<ConfigEntry> = Vec::new();
+
+    for (key, value) in config.section("gdcheck").into_iter().flatten() {
+        if key.as_str().starts_with("rules/") {
+            let name = key.clone().slice_range("rules/".len(), None);
+            let value = match value {
+                EntryValue::BooleanValue(value) => ConfigValue::Boolean(*value),
+                value => match value.as_string() {
+                    Some(value) => ConfigValue::String(value.as_str().to_owned()),
+                    None => ConfigValue::Other,
+                },
+            };
+
+            match name.as_str().find('/') {
+                Some(index) => entries.push((
+                    key.clone(),
+                    name.clone().slice_range(0, Some(index)),
+                    Some(name.slice_range(index + 1, None)),
+                    value,
+                )),
+                None => entries.push((key.clone(), name, None, value)),
+            }
+        }
+    }
+
+    // sections are unordered, but errors should come out in file order
+    entries.sort_by_key(|(key, _, _, _)| key.start);
+
+    apply_rule_config(entries, config_file, severities, options, report_error);
+}
+
+/// Apply the rule configuration in gdcheck.toml on top of `severities` and
+/// `options`, from entries like `AssignmentType = "warning"` under `[rules]`
+/// and `allow_private = true` under `[rules.RequireTypeAnnotations]`. Like
+/// project.godot's configuration, anything that can't be read is reported
+/// against `config_file` and otherwise ignored.
+pub fn read_toml_rule_config<F: FnMut(GDError)>(
+    code: Slice,
+    config_file: &ModuleID,
+    severities: &mut HashMap<Rule, RuleSeverity>,
+    options: &mut RuleOptions,
+    report_error: &mut F,
+) {
+    let spanned = |span: Option<std::ops::Range<usize>>| match span {
+        Some(span) => code.clone().slice_range(span.start, Some(span.end)),
+        None => code.clone().slice_range(0, Some(0)),
+    };
+    let mut report = |src: Slice, message: String| {
+        report_error(GDError::CheckError {
+            module_id: config_file.clone(),
+            src: Some(src),
+            message,
+        })
+    };
+
+    let document = match ImDocument::parse(code.as_str()) {
+        Ok(document) => document,
+        Err(error) => {
+            report(
+                spanned(error.span()),
+                format!("Invalid TOML: {}", error.message()),
+            );
+            return;
+        }
+    };
+
+    let rules = match document.as_table().get_key_value("rules") {
+        Some((_, item)) if item.is_table_like() => item.as_table_like().unwrap(),
+        Some((key, _)) => {
+            report(
+                spanned(key.span()),
+                String::from("Expected 'rules' to be a table"),
+            );
+            return;
+        }
+        None => return,
+    };
+    let value_of = |item: &Item| match item.as_value() {
+        Some(Value::Boolean(value)) => ConfigValue::Boolean(*value.value()),
+        Some(Value::String(value)) => ConfigValue::String(value.value().clone()),
+        _ => ConfigValue::Other,
+    };
+
+    let mut entries: Vec<ConfigEntry> = Vec::new();
+
+    for (name, item) in rules.iter() {
+        let (rule_key, _) = rules.get_key_value(name).unwrap();
+        let rule_key = spanned(rule_key.span());
+
+        match item.as_table_like() {
+            Some(rule_options) => {
+                for (option, value) in rule_options.iter() {
+                    let (option_key, _) = rule_options.get_key_value(option).unwrap();
+                    let option_key = spanned(option_key.span());
+
+                    entries.push((
+                        option_key.clone(),
+                        rule_key.clone(),
+                        Some(option_key),
+                        value_of(value),
+                    ));
+                }
+            }
+            None => entries.push((rule_key.clone(), rule_key, None, value_of(item))),
+        }
+    }
+
+    entries.sort_by_key(|(key, _, _, _)| key.start);
+
+    apply_rule_config(entries, config_file, severities, options, report_error);
+}
+
+fn apply_rule_config<F: FnMut(GDError)>(
+    entries: Vec<ConfigEntry>,
+    config_file: &ModuleID,
+    severities: &mut HashMap<Rule, RuleSeverity>,
+    options: &mut RuleOptions,
+    report_error: &mut F,
+) {
+    for (key, name, option, value) in entries {
+        let mut report = |message: String| {
+            report_error(GDError::CheckError {
+                module_id: config_file.clone(),
+                src: Some(key.clone()),
+                message,
+            })
+        };
+
+        let rule = match Rule::from_str(name.as_str()) {
+            Ok(rule) => rule,
+            Err(_) => {
+                report(format!("Unknown rule '{}'", name.as_str()));
+                continue;
+            }
+        };
+
+        match option {
+            Some(option) => match value {
+                ConfigValue::Boolean(value) => {
+                    if !options.set(rule, option.as_str(), value) {
+                        report(format!(
+                            "Unknown option '{}' for rule '{}'",
+                            option.as_str(),
+                            name.as_str()
+                        ));
+                    }
+                }
+                _ => report(format!(
+                    "Expected true or false for option '{}' of rule '{}'",
+                    option.as_str(),
+                    name.as_str()
+                )),
+            },
+            None => match value
+                .as_str()
+                .and_then(|value| RuleSeverity::from_str(value).ok())
+            {
+                Some(severity) => {
+                    severities.insert(rule, severity);
+                }
+                None => report(format!(
+                    "Invalid severity for rule '{}', expected \"error\", \"warning\", \"info\" or \"off\"",
+                    name.as_str()
+                )),
+            },
+        }
+    }
+}
+
+#[test]
+fn configured_severities() {
+    use super::check::check_source_in;
+    use std::{convert::TryFrom, rc::Rc};
+
+    let metadata =
+        |code: &str| GDProjectMetadata::try_from(Slice::new(Rc::new(code.to_owned()))).unwrap();
+    let config_file = ModuleID(Rc::new(String::from("project.godot")));
+
+    let mut errors = Vec::new();
+    let mut severities = HashMap::new();
+    let mut options = RuleOptions::default();
+    read_rule_config(
+        &metadata("[application]\nconfig/name=\"Test\"\n\n[gdcheck]\nrules/AssignmentType=\"warning\"\nrules/Bogus=\"error\"\nrules/StrictAny=\"loud\"\nrules/RequireTypeAnnotations/allow_private=true\nrules/RequireTypeAnnotations/everything=true\n"),
+        &config_file,
+        &mut severities,
+        &mut options,
+        &mut |error| errors.push(error),
+    );
+    assert_eq!(
+        severities,
+        HashMap::from([(Rule::AssignmentType, RuleSeverity::Warning)])
+    );
+    assert!(options.type_annotations.allow_private);
+    let messages: Vec<String> = errors
+        .into_iter()
+        .filter_map(|error| match error {
+            GDError::CheckError { message, .. } => Some(message),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            String::from("Unknown rule 'Bogus'"),
+            String::from("Invalid severity for rule 'StrictAny', expected \"error\", \"warning\", \"info\" or \"off\""),
+            String::from("Unknown option 'everything' for rule 'RequireTypeAnnotations'"),
+        ]
+    );
+
+    // gdcheck.toml overrides project.godot
+    let toml_code = |code: &str| Slice::new(Rc::new(code.to_owned()));
+    let mut toml = severities.clone();
+    read_toml_rule_config(
+        toml_code("# gdcheck.toml\n[rules]\nAssignmentType = 'off'\n\n[rules.RequireTypeAnnotations]\nallow_private = false\n"),
+        &config_file,
+        &mut toml,
+        &mut options,
+        &mut |error| panic!("{:?}", error),
+    );
+    assert_eq!(
+        toml,
+        HashMap::from([(Rule::AssignmentType, RuleSeverity::Off)])
+    );
+    assert_eq!(options, RuleOptions::default());
+
+    // options can be inline tables too, and anything unreadable is reported
+    let mut errors = Vec::new();
+    let mut inline_options = RuleOptions::default();
+    read_toml_rule_config(
+        toml_code("[rules]\nStrictAny = 1\nRequireTypeAnnotations = { allow_private = true, functions = \"no\" }\n"),
+        &config_file,
+        &mut HashMap::new(),
+        &mut inline_options,
+        &mut |error| errors.push(error),
+    );
+    read_toml_rule_config(
+        toml_code("[rules]\nAssignmentType = \"off\n"),
+        &config_file,
+        &mut HashMap::new(),
+        &mut inline_options,
+        &mut |error| errors.push(error),
+    );
+    assert!(inline_options.type_annotations.allow_private);
+    let messages: Vec<(String, String)> = errors
+        .into_iter()
+        .filter_map(|error| match error {
+            GDError::CheckError {
+                message,
+                src: Some(src),
+                ..
+            } => Some((src.as_str().to_owned(), message)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(
+        messages[..2],
+        [
+            (String::from("StrictAny"), String::from("Invalid severity for rule 'StrictAny', expected \"error\", \"warning\", \"info\" or \"off\"")),
+            (String::from("functions"), String::from("Expected true or false for option 'functions' of rule 'RequireTypeAnnotations'")),
+        ]
+    );
+    assert!(messages[2].1.starts_with("Invalid TOML: "));
+
+    let reported = |rule_severity: HashMap<Rule, RuleSeverity>| {
+        let project = crate::godot_project::GodotProject {
+            rule_severity,
+            ..crate::godot_project::GodotProject::for_test()
+        };
+        let mut severities = Vec::new();
+
+        for error in check_source_in(&project, "var x: bool = []\n") {
+            if let GDError::RuleViolation { rule, severity, .. } = error {
+                severities.push((rule, severity));
+            }
+        }
+
+        severities
+    };
+
+    assert_eq!(
+        reported(HashMap::new()),
+        vec![(Rule::AssignmentType, RuleSeverity::Error)]
+    );
+    assert_eq!(
+        reported(severities),
+        vec![(Rule::AssignmentType, RuleSeverity::Warning)]
+    );
+    assert_eq!(reported(toml), vec![]);
+
+    // mistakes that are errors by default can be configured too
+    let project = crate::godot_project::GodotProject {
+        rule_severity: HashMap::from([
+            (Rule::UndeclaredIdentifier, RuleSeverity::Warning),
+            (Rule::ArgumentCount, RuleSeverity::Off),
+            (Rule::ReturnType, RuleSeverity::Info),
+        ]),
+        ..crate::godot_project::GodotProject::for_test()
+    };
+    let violations: Vec<(Rule, RuleSeverity)> = check_source_in(
+        &project,
+        "func f() -> int:\n\tvar a = f(1)\n\treturn nope\nfunc g() -> int:\n\treturn\n",
+    )
+    .into_iter()
+    .filter_map(|error| match error {
+        GDError::RuleViolation { rule, severity, .. } => Some((rule, severity)),
+        _ => None,
+    })
+    .collect();
+    assert_eq!(
+        violations,
+        vec![
+            (Rule::UndeclaredIdentifier, RuleSeverity::Warning),
+            (Rule::ReturnType, RuleSeverity::Info),
+        ]
+    );
+
+    // and so can everything else the checker reports, down to turning it
+    // off entirely
+    let project = crate::godot_project::GodotProject {
+        rule_severity: HashMap::from([
+            (Rule::DivisionByZero, RuleSeverity::Off),
+            (Rule::UnknownMember, RuleSeverity::Off),
+            (Rule::InvalidOperation, RuleSeverity::Info),
+        ]),
+        ..crate::godot_project::GodotProject::for_test()
+    };
+    let violations: Vec<(Rule, RuleSeverity)> = check_source_in(
+        &project,
+        "const A = 1 / 0\nvar b = \"a\" - 1\nvar c = Vector2.ZERO.nope\nvar d = 99999999999999999999\n",
+    )
+    .into_iter()
+    .map(|error| match error {
+        GDError::RuleViolation { rule, severity, .. } => (rule, severity),
+        error => panic!("{:?} isn't a rule violation", error),
+    })
+    .collect();
+    assert_eq!(
+        violations,
+        vec![
+            (Rule::InvalidOperation, RuleSeverity::Info),
+            (Rule::IntegerOverflow, RuleSeverity::Error),
+        ]
+    );
+}
@@ -0,0 +1,323 @@
+use std::{cell::OnceCell, collections::HashMap, fmt::Debug};
+
+use crate::utils::slice::Slicable;
+
+use super::ast::*;
+
+/// The symbols declared directly in one lexical scope: a script or class
+/// body, a function's or lambda's parameters, a block, or a match arm
+#[derive(Clone, Default)]
+pub struct Scope {
+    bindings: HashMap<String, Binding>,
+
+    /// The values assigned (with a plain `=`) to each variable declared in
+    /// this scope, collected in one pass the first time they're needed
+    assignments: OnceCell<HashMap<String, Vec<AST<Expression>>>>,
+}
+
+#[derive(Clone)]
+struct Binding {
+    declaration: ASTAny,
+
+    /// Locals can only be used after they're declared. Everything else
+    /// (members, parameters, loop variables etc) is visible throughout its
+    /// scope.
+    visible_from: Option<usize>,
+}
+
+impl Scope {
+    fn declare(&mut self, name: &str, declaration: ASTAny, visible_from: Option<usize>) {
+        // the first declaration of a name is the one that counts
+        self.bindings.entry(name.to_owned()).or_insert(Binding {
+            declaration,
+            visible_from,
+        });
+    }
+
+    /// The declaration `name` refers to when used at `position` in the
+    /// source, if it's declared in this scope and visible there
+    pub fn lookup(&self, name: &str, position: usize) -> Option<&ASTAny> {
+        self.bindings
+            .get(name)
+            .filter(|binding| binding.visible_from.is_none_or(|from| position >= from))
+            .map(|binding| &binding.declaration)
+    }
+
+    /// The values assigned (with a plain `=`) anywhere in `node`, the node
+    /// this scope belongs to, to the variable it declares as `name`
+    pub fn assignments(&self, node: &ASTAny, name: &str) -> &[AST<Expression>] {
+        let assignments = self.assignments.get_or_init(|| {
+            let mut assignments: HashMap<String, Vec<AST<Expression>>> = HashMap::new();
+
+            for descendant in node.descendants() {
+                if let Any::AssignmentStatement(AssignmentStatement {
+                    target,
+                    value,
+                    operator: None,
+                }) = descendant.details()
+                {
+                    if let Expression::LocalIdentifier(LocalIdentifier { name }) = target.downcast()
+                    {
+                        let declared_here =
+                            self.bindings.get(name.as_str()).is_some_and(|binding| {
+                                target
+                                    .resolve_symbol(name.as_str())
+                                    .is_some_and(|resolved| resolved.ptr_eq(&binding.declaration))
+                            });
+
+                        if declared_here {
+                            assignments
+                                .entry(name.as_str().to_owned())
+                                .or_default()
+                                .push(value.clone());
+                        }
+                    }
+                }
+            }
+
+            assignments
+        });
+
+        assignments.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Every name declared in this scope
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.bindings.keys().map(|name| name.as_str())
+    }
+
+    /// Every name that's declared in this scope and visible at `position`
+    pub fn visible_names(&self, position: usize) -> impl Iterator<Item = &str> {
+        self.names()
+            .filter(move |name| self.lookup(name, position).is_some())
+    }
+}
+
+impl Debug for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+/// Attach a scope to every node in `script` that introduces one, so
+/// looking up a symbol only has to check the scopes enclosing it
+pub fn build_scopes(script: &AST<GDScript>) {
+    let root = script.clone().upcast();
+
+    for node in std::iter::once(root.clone()).chain(root.descendants()) {
+        if let Some(scope) = node_scope(&node) {
+            node.set_scope(scope);
+        }
+    }
+}
+
+fn node_scope(node: &ASTAny) -> Option<Scope> {
+    let mut scope = Scope::default();
+
+    match node.details() {
+        Any::GDScript(GDScript { declarations })
+        | Any::ClassDeclaration(ClassDeclaration {
+            name: _,
+            declarations,
+        }) => {
+            for decl in declarations {
+                declare_member(&mut scope, decl);
+            }
+        }
+        Any::FuncDeclaration(FuncDeclaration {
+            is_static: _,
+            name: _,
+            args,
+            return_type: _,
+            body: _,
+        })
+        | Any::Lambda(Lambda {
+            args,
+            return_type: _,
+            body: _,
+        }) => {
+            for arg in args {
+                let name = arg.downcast().name.downcast().name;
+
+                scope.declare(name.as_str(), arg.clone().upcast(), None);
+            }
+        }
+        Any::Block(Block { statements }) => {
+            // a loop's variable is bound inside its body, but not in the
+            // expression being iterated over
+            if let Some(Any::ForLoop(ForLoop {
+                item_name,
+                item_type: _,
+                iteree: _,
+                body,
+            })) = node.parent().as_ref().map(|parent| parent.details())
+            {
+                if body.ptr_eq(node) {
+                    let for_loop = node.parent().unwrap();
+
+                    scope.declare(item_name.downcast().name.as_str(), for_loop, None);
+                }
+            }
+
+            for statement in statements {
+                if let Statement::ValueDeclaration(ValueDeclaration {
+                    is_const: _,
+                    name,
+                    declared_type: _,
+                    is_inferred: _,
+                    value: _,
+                }) = statement.downcast()
+                {
+                    scope.declare(
+                        name.downcast().name.as_str(),
+                        statement.clone().upcast(),
+                        Some(statement.slice().end),
+                    );
+                }
+            }
+        }
+        Any::MatchArm(MatchArm {
+            patterns,
+            guard: _,
+            body: _,
+        }) => {
+            for pattern in patterns {
+                let pattern = pattern.clone().upcast();
+
+                for node in std::iter::once(pattern.clone()).chain(pattern.descendants()) {
+                    if let Some(BindingPattern { name }) = node.try_downcast::<BindingPattern>() {
+                        scope.declare(name.downcast().name.as_str(), node.clone(), None);
+                    }
+                }
+            }
+        }
+        _ => return None,
+    }
+
+    Some(scope)
+}
+
+/// Whether code in an inner class can refer to `declaration`, a member of
+/// a class enclosing it. An inner class doesn't share its outer class's
+/// instance, so only constants, enums and other inner classes are visible
+/// from it.
+pub fn visible_to_inner_classes(declaration: &ASTAny) -> bool {
+    matches!(
+        declaration.details(),
+        Any::ValueDeclaration(ValueDeclaration {
+            is_const: true,
+            name: _,
+            declared_type: _,
+            is_inferred: _,
+            value: _,
+        }) | Any::EnumDeclaration(_)
+            | Any::ClassDeclaration(_)
+    )
+}
+
+fn declare_member(scope: &mut Scope, decl: &AST<Declaration>) {
+    let declaration = decl.clone().upcast();
+
+    match decl.downcast() {
+        Declaration::ValueDeclaration(ValueDeclaration {
+            is_const: _,
+            name,
+            declared_type: _,
+            is_inferred: _,
+            value: _,
+        })
+        | Declaration::FuncDeclaration(FuncDeclaration {
+            is_static: _,
+            name,
+            args: _,
+            return_type: _,
+            body: _,
+        })
+        | Declaration::SignalDeclaration(SignalDeclaration { name, args: _ })
+        | Declaration::ClassDeclaration(ClassDeclaration {
+            name,
+            declarations: _,
+        }) => scope.declare(name.downcast().name.as_str(), declaration, None),
+        Declaration::EnumDeclaration(EnumDeclaration { name, variants }) => match name {
+            Some(name) => scope.declare(name.downcast().name.as_str(), declaration, None),
+            // members of unnamed enums are constants in the enclosing scope
+            None => {
+                for (variant, _) in variants {
+                    scope.declare(variant.downcast().name.as_str(), declaration.clone(), None);
+                }
+            }
+        },
+        Declaration::ExtendsDeclaration(_)
+        | Declaration::ClassNameDeclaration(_)
+        | Declaration::Annotation(_) => {}
+    }
+}
+
+#[test]
+fn scopes() {
+    use super::parse::parse_script;
+    use crate::utils::slice::Slice;
+    use std::rc::Rc;
+
+    let code = "var a = true\nfunc f(x):\n\tvar b = null\n\tvar c = [true]\n\tfor i in [null]:\n\t\tpass\n";
+    let module_id = ModuleID(Rc::new(String::from("test.gd")));
+    let script = parse_script(module_id, Slice::new(Rc::new(code.to_owned()))).unwrap();
+    let nodes = script.clone().upcast().descendants();
+    let array_of = |f: fn(&Any) -> bool| {
+        nodes
+            .iter()
+            .find(|node| match node.details() {
+                Any::ArrayLiteral(ArrayLiteral { members }) => f(members[0].details()),
+                _ => false,
+            })
+            .unwrap()
+    };
+
+    let b_value = nodes
+        .iter()
+        .find(|node| matches!(node.details(), Any::NullLiteral(_)))
+        .unwrap();
+    let c_value = array_of(|member| matches!(member, Any::BooleanLiteral(_)));
+    let iteree = array_of(|member| matches!(member, Any::NullLiteral(_)));
+    let pass = nodes
+        .iter()
+        .find(|node| matches!(node.details(), Any::Pass(_)))
+        .unwrap();
+
+    // locals are only visible after they're declared
+    assert!(b_value.resolve_symbol("b").is_none());
+    assert!(c_value.resolve_symbol("b").is_some());
+    assert!(c_value.resolve_symbol("c").is_none());
+
+    assert!(c_value.resolve_symbol("x").is_some());
+    assert!(c_value.resolve_symbol("a").is_some());
+    assert!(c_value.resolve_symbol("f").is_some());
+
+    // loop variables are only bound inside the loop's body
+    assert!(iteree.resolve_symbol("i").is_none());
+    assert!(pass.resolve_symbol("i").is_some());
+    assert!(pass.resolve_symbol("x").is_some());
+}
+
+#[test]
+fn inner_classes() {
+    use super::check::{check_source, located};
+
+    // an inner class sees its outer class's constants and classes, but not
+    // the members of its instance
+    let code = "const LIMIT = 3\nvar health = 10\nfunc heal():\n\tpass\nclass Other:\n\tvar o = 1\nclass Inner:\n\tvar limit: int = LIMIT\n\tvar other = Other\n\tvar hp = health\n\tvar cure = heal\n";
+
+    assert_eq!(
+        located(check_source(code)),
+        vec![
+            (
+                String::from("health"),
+                String::from("Identifier 'health' is not declared in the current scope")
+            ),
+            (
+                String::from("heal"),
+                String::from("Identifier 'heal' is not declared in the current scope")
+            ),
+        ]
+    );
+}
@@ -0,0 +1,320 @@
+use std::rc::Rc;
+
+use crate::utils::slice::{Slicable, Slice};
+
+use super::{ast::*, check::CheckContext, infer_type::GDSCRIPT_FUNCTIONS, rules::Rule};
+
+/// A declaration whose name is already taken where it's declared
+pub struct Conflict {
+    pub rule: Rule,
+    pub name: AST<PlainIdentifier>,
+    pub message: String,
+}
+
+impl ASTAny {
+    /// If this node declares a name, the first thing that name collides
+    /// with or hides: an earlier declaration in the same scope, a
+    /// declaration in an enclosing scope, an inherited member, or a global
+    pub fn declaration_conflict<'a>(&self, ctx: CheckContext<'a>) -> Option<Conflict> {
+        let godot_project = ctx.godot_project;
+        let (name, kind) = declared_name(self)?;
+        let scope_node = declaring_scope(self)?;
+        let symbol = name.downcast().name;
+        let symbol = symbol.as_str();
+        let is_member = matches!(
+            scope_node.details(),
+            Any::GDScript(_) | Any::ClassDeclaration(_)
+        );
+        let is_function = matches!(self.details(), Any::FuncDeclaration(_));
+        let declared = format!("{} '{}'", capitalized(kind), symbol);
+        let conflict = |rule: Rule, message: String| {
+            Some(Conflict {
+                rule,
+                name: name.clone(),
+                message,
+            })
+        };
+
+        // the first declaration of a name in a scope is the one it's bound
+        // to, so any other is a duplicate
+        if let Some(previous) = scope_node
+            .scope()
+            .and_then(|scope| scope.lookup(symbol, usize::MAX))
+        {
+            if !previous.ptr_eq(self) {
+                return conflict(
+                    Rule::DuplicateDeclaration,
+                    format!(
+                        "{} is already declared at {}",
+                        declared,
+                        location(previous, ctx)
+                    ),
+                );
+            }
+        }
+
+        if !is_member {
+            if let Some(outer) = scope_node.resolve_symbol(symbol) {
+                return conflict(
+                    Rule::ShadowedVariable,
+                    format!(
+                        "{} shadows the {} declared at {}",
+                        declared,
+                        describe(&outer).0,
+                        location(&outer, ctx)
+                    ),
+                );
+            }
+        }
+
+        if let Some(base) = base_class(&scope_node, ctx) {
+            if let Some((script, inherited)) =
+                godot_project.find_in_script_classes(&base, |script| {
+                    script
+                        .declaration(symbol)
+                        .map(|inherited| (script.clone(), inherited))
+                })
+            {
+                // overriding a method is what methods are for
+                if is_function && matches!(inherited.details(), Any::FuncDeclaration(_)) {
+                    return None;
+                }
+
+                let class = godot_project
+                    .script_class_name(&script)
+                    .unwrap_or_else(|| base.clone());
+
+                return conflict(
+                    Rule::ShadowedVariableBaseClass,
+                    format!(
+                        "{} shadows the {} inherited from {}, declared at {}",
+                        declared,
+                        describe(&inherited).0,
+                        class,
+                        location(&inherited, ctx)
+                    ),
+                );
+            }
+
+            if let Some((inherited_kind, class)) =
+                godot_project.find_in_engine_classes(&base, |engine_class| {
+                    let inherited_kind = if engine_class.properties.contains_key(symbol) {
+                        "property"
+                    } else if engine_class.methods.contains_key(symbol) {
+                        "method"
+                    } else if engine_class.signals.contains_key(symbol) {
+                        "signal"
+                    } else if engine_class.constant(symbol).is_some()
+                        || engine_class.enums.contains_key(symbol)
+                    {
+                        "constant"
+                    } else {
+                        return None;
+                    };
+
+                    Some((inherited_kind, engine_class.name.clone()))
+                })
+            {
+                // including the engine's virtual methods, like `_ready()`
+                if is_function && inherited_kind == "method" {
+                    return None;
+                }
+
+                return conflict(
+                    Rule::ShadowedVariableBaseClass,
+                    format!(
+                        "{} shadows the {} inherited from {}",
+                        declared, inherited_kind, class
+                    ),
+                );
+            }
+        }
+
+        let global =
+            if let Some(script) = godot_project.script_class(symbol) {
+                let class_name = script.downcast().declarations.into_iter().find_map(|decl| {
+                    match decl.downcast() {
+                        Declaration::ClassNameDeclaration(ClassNameDeclaration { class_name }) => {
+                            Some(class_name)
+                        }
+                        _ => None,
+                    }
+                });
+
+                match class_name {
+                    Some(class_name) => format!(
+                        "the global class declared at {}",
+                        location(&class_name.upcast(), ctx)
+                    ),
+                    None => String::from("a global class"),
+                }
+            } else if godot_project.class_exists(symbol) {
+                String::from("a global class")
+            } else if godot_project
+                .metadata
+                .autoloads()
+                .iter()
+                .any(|autoload| autoload.enabled && autoload.name == symbol)
+            {
+                String::from("an autoload")
+            } else if godot_project.engine_api.singletons.contains_key(symbol) {
+                String::from("an engine singleton")
+            } else if godot_project
+                .engine_api
+                .utility_functions
+                .contains_key(symbol)
+                || GDSCRIPT_FUNCTIONS.contains(&symbol)
+            {
+                String::from("a built-in function")
+            } else {
+                return None;
+            };
+
+        conflict(
+            Rule::ShadowedGlobalIdentifier,
+            format!("{} shadows {}", declared, global),
+        )
+    }
+}
+
+/// The name a node declares and what kind of thing it is, if it's a
+/// declaration that binds a single name
+fn declared_name(node: &ASTAny) -> Option<(AST<PlainIdentifier>, &'static str)> {
+    match node.details() {
+        Any::ValueDeclaration(ValueDeclaration {
+            is_const,
+            name,
+            declared_type: _,
+            is_inferred: _,
+            value: _,
+        }) => Some((
+            name.clone(),
+            if *is_const { "constant" } else { "variable" },
+        )),
+        Any::FuncDeclaration(FuncDeclaration {
+            is_static: _,
+            name,
+            args: _,
+            return_type: _,
+            body: _,
+        }) => Some((name.clone(), "function")),
+        Any::SignalDeclaration(SignalDeclaration { name, args: _ }) => {
+            Some((name.clone(), "signal"))
+        }
+        Any::ClassDeclaration(ClassDeclaration {
+            name,
+            declarations: _,
+        }) => Some((name.clone(), "class")),
+        Any::EnumDeclaration(EnumDeclaration {
+            name: Some(name),
+            variants: _,
+        }) => Some((name.clone(), "enum")),
+        Any::FuncArg(FuncArg {
+            name,
+            declared_type: _,
+            default_value: _,
+        }) => Some((name.clone(), "parameter")),
+        Any::ForLoop(ForLoop {
+            item_name,
+            item_type: _,
+            iteree: _,
+            body: _,
+        }) => Some((item_name.clone(), "loop variable")),
+        Any::BindingPattern(BindingPattern { name }) => Some((name.clone(), "match binding")),
+        _ => None,
+    }
+}
+
+/// The node whose scope a declaration's name is bound in
+fn declaring_scope(node: &ASTAny) -> Option<ASTAny> {
+    let scope_node = match node.details() {
+        Any::ForLoop(ForLoop {
+            item_name: _,
+            item_type: _,
+            iteree: _,
+            body,
+        }) => body.clone().upcast(),
+        Any::BindingPattern(_) => node.find_parent_of_type::<MatchArm>()?.upcast(),
+        _ => node.parent()?,
+    };
+
+    // signal parameters aren't bound anywhere
+    scope_node.scope()?;
+
+    Some(scope_node)
+}
+
+/// What kind of thing `declaration` declares, and where its name is
+fn describe(declaration: &ASTAny) -> (&'static str, Slice) {
+    match declared_name(declaration) {
+        Some((name, kind)) => (kind, name.slice().clone()),
+        // members of unnamed enums are bound to the enum itself
+        None => ("enum value", declaration.slice().clone()),
+    }
+}
+
+/// `path:line:column` of the name `declaration` declares
+fn location<'a>(declaration: &ASTAny, ctx: CheckContext<'a>) -> String {
+    let module_id = declaration
+        .find_parent_of_type::<GDScript>()
+        .and_then(|script| {
+            ctx.godot_project
+                .scripts
+                .iter()
+                .find(|(_, other)| other.ptr_eq(&script))
+                .map(|(module_id, _)| module_id.clone())
+        })
+        .unwrap_or_else(|| ctx.module_id.clone());
+    let (line, column) = match declaration.details() {
+        Any::PlainIdentifier(_) => declaration.slice().line_and_column(),
+        _ => describe(declaration).1.line_and_column(),
+    };
+
+    format!("{}:{}:{}", module_id.0, line, column)
+}
+
+/// The class that members declared in `scope_node` are inherited from, if
+/// they're members of a script rather than locals or members of an inner
+/// class
+fn base_class<'a>(scope_node: &ASTAny, ctx: CheckContext<'a>) -> Option<Rc<String>> {
+    let godot_project = ctx.godot_project;
+
+    if scope_node
+        .find_parent_of_type::<ClassDeclaration>()
+        .is_some()
+    {
+        return None;
+    }
+
+    let script = scope_node.find_parent_of_type::<GDScript>()?;
+
+    match godot_project.script_class_name(&script) {
+        Some(class) => godot_project.base_class(&class),
+        None => {
+            let extends =
+                script
+                    .downcast()
+                    .declarations
+                    .into_iter()
+                    .find_map(|decl| match decl.downcast() {
+                        Declaration::ExtendsDeclaration(ExtendsDeclaration { extends_class }) => {
+                            Some(extends_class.downcast().name.as_str().to_owned())
+                        }
+                        _ => None,
+                    });
+
+            Some(Rc::new(
+                extends.unwrap_or_else(|| String::from("RefCounted")),
+            ))
+        }
+    }
+}
+
+fn capitalized(kind: &str) -> String {
+    let mut chars = kind.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
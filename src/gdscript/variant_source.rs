@@ -0,0 +1,88 @@
+use super::{ast::*, gd_type::Type, infer_type::InferTypeContext};
+
+impl AST<Expression> {
+    /// Where the `Variant` this expression evaluates to comes from: the
+    /// innermost expression that produced it, or the untyped parameter it
+    /// was passed in through. Untyped variables are followed back to their
+    /// initial values.
+    pub fn variant_source<'a>(&self, ctx: InferTypeContext<'a>) -> ASTAny {
+        variant_source(self, ctx, &mut Vec::new())
+    }
+}
+
+fn variant_source<'a>(
+    expr: &AST<Expression>,
+    ctx: InferTypeContext<'a>,
+    visited: &mut Vec<ASTAny>,
+) -> ASTAny {
+    let is_variant = |expr: &AST<Expression>| expr.infer_type(ctx) == Type::Any;
+
+    match expr.downcast() {
+        Expression::LocalIdentifier(LocalIdentifier { name }) => {
+            let declaration = match expr.resolve_symbol(name.as_str()) {
+                Some(declaration) if !visited.iter().any(|other| other.ptr_eq(&declaration)) => {
+                    declaration
+                }
+                _ => return expr.clone().upcast(),
+            };
+
+            visited.push(declaration.clone());
+
+            match declaration.details() {
+                Any::ValueDeclaration(ValueDeclaration {
+                    is_const: _,
+                    name: _,
+                    declared_type: None,
+                    is_inferred: _,
+                    value: Some(value),
+                }) if is_variant(value) => variant_source(value, ctx, visited),
+                Any::FuncArg(FuncArg {
+                    name,
+                    declared_type: None,
+                    default_value: _,
+                }) => name.clone().upcast(),
+                _ => expr.clone().upcast(),
+            }
+        }
+        Expression::UnaryOperation(UnaryOperation { op: _, subject }) if is_variant(&subject) => {
+            variant_source(&subject, ctx, visited)
+        }
+        Expression::BinaryOperation(BinaryOperation { op, left, right })
+            if op.downcast() != BinaryOperator::As =>
+        {
+            match (is_variant(&left), is_variant(&right)) {
+                (true, _) => variant_source(&left, ctx, visited),
+                (false, true) => variant_source(&right, ctx, visited),
+                (false, false) => expr.clone().upcast(),
+            }
+        }
+        Expression::TernaryExpression(TernaryExpression {
+            condition: _,
+            if_true,
+            if_false,
+        }) => match (is_variant(&if_true), is_variant(&if_false)) {
+            (true, _) => variant_source(&if_true, ctx, visited),
+            (false, true) => variant_source(&if_false, ctx, visited),
+            (false, false) => expr.clone().upcast(),
+        },
+
+        // anything done with a Variant gives another Variant
+        Expression::PropertyAccess(PropertyAccess {
+            subject,
+            property: _,
+        })
+        | Expression::Subscript(Subscript { subject, index: _ })
+            if is_variant(&subject) =>
+        {
+            variant_source(&subject, ctx, visited)
+        }
+        Expression::FunctionCall(FunctionCall { function, args: _ }) => match function.downcast() {
+            Expression::PropertyAccess(PropertyAccess {
+                subject,
+                property: _,
+            }) if is_variant(&subject) => variant_source(&subject, ctx, visited),
+            _ => expr.clone().upcast(),
+        },
+        _ => expr.clone().upcast(),
+    }
+}
@@ -1,16 +1,644 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path, rc::Rc};
 
 use crate::{
-    gdproject_metadata::ast::GDProjectMetadata,
+    extension_api::ast::{EngineClass, ExtensionAPI},
+    gdproject_metadata::ast::{GDProjectMetadata, GlobalScriptClass},
     gdscript::{
-        ast::{GDScript, ModuleID},
-        rules::{Rule, RuleSeverity},
+        ast::{ClassNameDeclaration, Declaration, ExtendsDeclaration, GDScript, ModuleID, AST},
+        rules::{Rule, RuleOptions, RuleSeverity},
     },
+    utils::{errors::GDError, slice::Slicable},
 };
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct GodotProject {
     pub metadata: GDProjectMetadata,
     pub rule_severity: HashMap<Rule, RuleSeverity>,
-    pub scripts: HashMap<ModuleID, GDScript>,
+    pub rule_options: RuleOptions,
+    pub scripts: HashMap<ModuleID, AST<GDScript>>,
+    pub script_index: ScriptIndex,
+    pub engine_api: Rc<ExtensionAPI>,
+}
+
+/// Project-wide symbol table for finding scripts by their `class_name` or
+/// their `res://` path
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScriptIndex {
+    pub class_names: HashMap<String, ModuleID>,
+    pub paths: HashMap<String, ModuleID>,
+
+    /// The name each script's class goes by in types: its `class_name` if
+    /// it has one, otherwise its `res://` path
+    pub script_names: HashMap<ModuleID, Rc<String>>,
+
+    /// Scenes' root nodes by the scenes' `res://` paths
+    pub scene_roots: HashMap<String, SceneRoot>,
+
+    /// The classes the editor has registered as global, if the project
+    /// includes its list of them
+    pub global_classes: Option<GlobalClassRegistry>,
+}
+
+/// The editor's list of global script classes, from Godot 3's
+/// `project.godot` or Godot 4's `.godot/global_script_class_cache.cfg`
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlobalClassRegistry {
+    /// The file the list came from
+    pub source: ModuleID,
+    pub classes: Vec<GlobalScriptClass>,
+}
+
+impl ScriptIndex {
+    pub fn new(scripts: &HashMap<ModuleID, AST<GDScript>>, project_root: &Path) -> Self {
+        let mut index = ScriptIndex::default();
+
+        for (module_id, script) in scripts {
+            for decl in script.downcast().declarations {
+                if let Declaration::ClassNameDeclaration(ClassNameDeclaration { class_name }) =
+                    decl.downcast()
+                {
+                    index.class_names.insert(
+                        class_name.downcast().name.as_str().to_owned(),
+                        module_id.clone(),
+                    );
+                }
+            }
+
+            if let Some(path) = res_path(Path::new(module_id.0.as_str()), project_root) {
+                index.paths.insert(path, module_id.clone());
+            }
+        }
+
+        for (name, module_id) in index.class_names.iter().chain(index.paths.iter()) {
+            index
+                .script_names
+                .entry(module_id.clone())
+                .or_insert_with(|| Rc::new(name.clone()));
+        }
+
+        index
+    }
+
+    /// Record the root node of the scene at `path`, given the scene's code
+    pub fn add_scene(&mut self, path: &Path, project_root: &Path, code: &str) {
+        if let (Some(path), Some(root)) = (res_path(path, project_root), SceneRoot::parse(code)) {
+            self.scene_roots.insert(path, root);
+        }
+    }
+}
+
+/// The `res://` path of a file in the project
+fn res_path(path: &Path, project_root: &Path) -> Option<String> {
+    let relative = path.strip_prefix(project_root).ok()?;
+
+    Some(format!(
+        "res://{}",
+        relative.to_string_lossy().replace('\\', "/")
+    ))
+}
+
+/// The root node of a scene, which is what an autoloaded scene is
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneRoot {
+    /// The engine class of the node, unless it's an instance of another
+    /// scene
+    pub node_type: Option<String>,
+
+    /// The `res://` path of the node's script
+    pub script: Option<String>,
+}
+
+impl SceneRoot {
+    /// Read the root node out of a `.tscn` file. Only the section headers
+    /// and the root node's `script` property are looked at.
+    pub fn parse(code: &str) -> Option<SceneRoot> {
+        let mut ext_resources: HashMap<String, String> = HashMap::new();
+        let mut root: Option<SceneRoot> = None;
+
+        for line in code.lines().map(str::trim) {
+            if line.starts_with("[ext_resource") {
+                if let (Some(id), Some(path)) =
+                    (header_attribute(line, "id"), header_attribute(line, "path"))
+                {
+                    ext_resources.insert(id, path);
+                }
+            } else if line.starts_with('[') && root.is_some() {
+                // past the root node's properties
+                break;
+            } else if line.starts_with("[node") {
+                root = Some(SceneRoot {
+                    node_type: header_attribute(line, "type"),
+                    script: None,
+                });
+            } else if let (Some(root), Some(value)) =
+                (root.as_mut(), line.strip_prefix("script = "))
+            {
+                // `ExtResource("1_abc")`, or `ExtResource( 1 )` in older
+                // versions
+                let id = value
+                    .trim_start_matches("ExtResource(")
+                    .trim_end_matches(')')
+                    .trim()
+                    .trim_matches('"');
+
+                root.script = ext_resources.get(id).cloned();
+            }
+        }
+
+        root
+    }
+}
+
+/// The value of the attribute `name` in a section header like
+/// `[node name="Player" type="CharacterBody2D"]`
+fn header_attribute(header: &str, name: &str) -> Option<String> {
+    let start = header.find(&format!(" {}=", name))? + name.len() + 2;
+    let rest = &header[start..];
+
+    let value = match rest.strip_prefix('"') {
+        Some(quoted) => &quoted[..quoted.find('"')?],
+        None => rest.split([' ', ']']).next()?,
+    };
+
+    Some(value.to_owned())
+}
+
+impl GodotProject {
+    /// How violations of `rule` are reported, as configured by the project
+    pub fn severity(&self, rule: Rule) -> RuleSeverity {
+        self.rule_severity
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_severity())
+    }
+
+    /// The script that declares `class_name <class>`, or whose `res://` path
+    /// is `class` (the name scripts without a `class_name` go by)
+    pub fn script_class(&self, class: &str) -> Option<&AST<GDScript>> {
+        self.script_index
+            .class_names
+            .get(class)
+            .or_else(|| self.script_index.paths.get(class))
+            .and_then(|module_id| self.scripts.get(module_id))
+    }
+
+    /// The script at the `res://` path `path`
+    pub fn script_at(&self, path: &str) -> Option<&AST<GDScript>> {
+        self.script_index
+            .paths
+            .get(path)
+            .and_then(|module_id| self.scripts.get(module_id))
+    }
+
+    /// The name the class declared by `script` goes by in types: its
+    /// `class_name` if it has one, otherwise its `res://` path
+    pub fn script_class_name(&self, script: &AST<GDScript>) -> Option<Rc<String>> {
+        let (module_id, _) = self
+            .scripts
+            .iter()
+            .find(|(_, other)| other.ptr_eq(script))?;
+
+        self.script_index.script_names.get(module_id).cloned()
+    }
+
+    /// The registry entry for the global class `class`, which may be
+    /// written in a language other than GDScript
+    pub fn registered_class(&self, class: &str) -> Option<&GlobalScriptClass> {
+        self.script_index
+            .global_classes
+            .as_ref()?
+            .classes
+            .iter()
+            .find(|entry| entry.class.as_str() == class)
+    }
+
+    pub fn class_exists(&self, class: &str) -> bool {
+        self.engine_api.class(class).is_some()
+            || self.script_class(class).is_some()
+            || self.registered_class(class).is_some()
+    }
+
+    /// The class that `class` directly inherits from, whether it's an
+    /// engine class or a script's `class_name`
+    pub fn base_class(&self, class: &str) -> Option<Rc<String>> {
+        if let Some(engine_class) = self.engine_api.class(class) {
+            return engine_class
+                .inherits
+                .as_ref()
+                .map(|base| Rc::new(base.to_owned()));
+        }
+
+        let script = match self.script_class(class) {
+            Some(script) => script,
+            None => {
+                return self
+                    .registered_class(class)
+                    .map(|entry| Rc::new(entry.base.as_str().to_owned()))
+            }
+        };
+        let extends = script
+            .downcast()
+            .declarations
+            .iter()
+            .find_map(|decl| match decl.downcast() {
+                Declaration::ExtendsDeclaration(ExtendsDeclaration { extends_class }) => {
+                    Some(extends_class.downcast().name.as_str().to_owned())
+                }
+                _ => None,
+            })
+            // scripts without an `extends` are RefCounted
+            .unwrap_or_else(|| String::from("RefCounted"));
+
+        // a script extended by its path still goes by its class_name
+        match self.script_class(&extends) {
+            Some(base) => self.script_class_name(base),
+            None => Some(Rc::new(extends)),
+        }
+    }
+
+    /// If `class` (indirectly) inherits from itself, the chain of classes
+    /// that leads back around to it
+    pub fn inheritance_cycle(&self, class: &str) -> Option<Vec<Rc<String>>> {
+        let mut chain = vec![Rc::new(class.to_owned())];
+
+        loop {
+            let base = self.base_class(chain.last()?)?;
+
+            if base.as_str() == class {
+                chain.push(base);
+                return Some(chain);
+            }
+
+            // a cycle further up the chain that `class` isn't part of
+            if chain.contains(&base) {
+                return None;
+            }
+
+            chain.push(base);
+        }
+    }
+
+    /// Whether `class` is `ancestor` or (indirectly) inherits from it
+    pub fn inherits(&self, class: &str, ancestor: &str) -> bool {
+        self.ancestors(class)
+            .iter()
+            .any(|other| other.as_str() == ancestor)
+    }
+
+    /// `class` itself, followed by every class it (indirectly) inherits from
+    pub fn ancestors(&self, class: &str) -> Vec<Rc<String>> {
+        let mut ancestors: Vec<Rc<String>> = Vec::new();
+        let mut current = Some(Rc::new(class.to_owned()));
+
+        while let Some(some_current) = current {
+            // guard against inheritance cycles between scripts
+            if ancestors.contains(&some_current) {
+                break;
+            }
+
+            current = self.base_class(&some_current);
+            ancestors.push(some_current);
+        }
+
+        ancestors
+    }
+
+    /// Report `class_name`s missing from the global class registry, and
+    /// entries in it that no script declares anymore
+    pub fn check_global_classes<F: FnMut(GDError)>(&self, report_error: &mut F) {
+        let registry = match &self.script_index.global_classes {
+            Some(registry) => registry,
+            None => return,
+        };
+        let severity = self.severity(Rule::GlobalClassRegistry);
+
+        if severity == RuleSeverity::Off {
+            return;
+        }
+
+        for (module_id, script) in &self.scripts {
+            for decl in script.downcast().declarations {
+                if let Declaration::ClassNameDeclaration(ClassNameDeclaration { class_name }) =
+                    decl.downcast()
+                {
+                    let name = class_name.downcast().name;
+
+                    if self.registered_class(name.as_str()).is_none() {
+                        report_error(GDError::RuleViolation {
+                            module_id: module_id.clone(),
+                            src: Some(class_name.slice().clone()),
+                            rule: Rule::GlobalClassRegistry,
+                            severity,
+                            message: format!(
+                                "Class '{}' is missing from the global script class registry",
+                                name.as_str()
+                            ),
+                            fix: None,
+                        });
+                    }
+                }
+            }
+        }
+
+        for entry in &registry.classes {
+            // classes in other languages aren't declared in scripts we check
+            if entry.language.as_str() != "GDScript" {
+                continue;
+            }
+
+            let declared = self
+                .script_at(entry.path.as_str())
+                .and_then(|script| self.script_class_name(script));
+
+            if declared.as_ref().map(|declared| declared.as_str()) != Some(entry.class.as_str()) {
+                report_error(GDError::RuleViolation {
+                    module_id: registry.source.clone(),
+                    src: Some(entry.class.clone()),
+                    rule: Rule::GlobalClassRegistry,
+                    severity,
+                    message: format!(
+                        "Stale global script class '{}': {} doesn't declare it",
+                        entry.class.as_str(),
+                        entry.path.as_str()
+                    ),
+                    fix: None,
+                });
+            }
+        }
+    }
+
+    /// The first engine class in `class`'s inheritance chain for which `f`
+    /// finds something, for looking up inherited methods, properties etc.
+    pub fn find_in_engine_classes<T, F: Fn(&EngineClass) -> Option<T>>(
+        &self,
+        class: &str,
+        f: F,
+    ) -> Option<T> {
+        self.ancestors(class)
+            .iter()
+            .filter_map(|ancestor| self.engine_api.class(ancestor))
+            .find_map(f)
+    }
+
+    /// The first script class in `class`'s inheritance chain for which `f`
+    /// finds something, for looking up inherited script members
+    pub fn find_in_script_classes<T, F: Fn(&AST<GDScript>) -> Option<T>>(
+        &self,
+        class: &str,
+        f: F,
+    ) -> Option<T> {
+        self.ancestors(class)
+            .iter()
+            .filter_map(|ancestor| self.script_class(ancestor))
+            .find_map(f)
+    }
+}
+
+#[cfg(test)]
+impl GodotProject {
+    /// An empty project with the bundled engine API
+    pub fn for_test() -> Self {
+        GodotProject {
+            metadata: GDProjectMetadata::new(),
+            rule_severity: HashMap::new(),
+            rule_options: RuleOptions::default(),
+            scripts: HashMap::new(),
+            script_index: ScriptIndex::default(),
+            engine_api: ExtensionAPI::bundled(),
+        }
+    }
+
+    /// This project with `sources`, pairs of a path under `/project` and a
+    /// script's code, parsed and indexed
+    pub fn with_scripts(mut self, sources: &[(&str, &str)]) -> Self {
+        use crate::{gdscript::parse::parse_script, utils::slice::Slice};
+
+        for (path, code) in sources {
+            let module_id = ModuleID(Rc::new(String::from(*path)));
+            let script =
+                parse_script(module_id.clone(), Slice::new(Rc::new((*code).to_owned()))).unwrap();
+
+            self.scripts.insert(module_id, script);
+        }
+
+        self.script_index = ScriptIndex::new(&self.scripts, Path::new("/project"));
+        self
+    }
+}
+
+#[test]
+fn inheritance_chains() {
+    use crate::gdscript::check::Checkable;
+
+    let sources = [
+        (
+            "/project/enemy.gd",
+            "class_name Enemy\nextends Node2D\nsignal died(cause: String)\n",
+        ),
+        ("/project/boss.gd", "extends \"res://enemy.gd\"\n"),
+        ("/project/a.gd", "class_name A\nextends B\n"),
+        ("/project/b.gd", "class_name B\nextends A\n"),
+    ];
+    let project = GodotProject::for_test().with_scripts(&sources);
+    let script = |path: &str| &project.scripts[&ModuleID(Rc::new(String::from(path)))];
+
+    // scripts go by their class_name, or else their path
+    assert_eq!(
+        project.script_class_name(script("/project/enemy.gd")),
+        Some(Rc::new(String::from("Enemy")))
+    );
+    assert_eq!(
+        project.script_class_name(script("/project/boss.gd")),
+        Some(Rc::new(String::from("res://boss.gd")))
+    );
+    assert!(project.inherits("res://boss.gd", "Enemy"));
+    assert!(project.inherits("res://boss.gd", "Node"));
+    assert!(project
+        .find_in_script_classes("res://boss.gd", |script| script.declaration("died"))
+        .is_some());
+    assert_eq!(project.inheritance_cycle("res://boss.gd"), None);
+    assert_eq!(
+        project.inheritance_cycle("A"),
+        Some(vec![
+            Rc::new(String::from("A")),
+            Rc::new(String::from("B")),
+            Rc::new(String::from("A"))
+        ])
+    );
+
+    let module_id = ModuleID(Rc::new(String::from("/project/a.gd")));
+    let mut errors = Vec::new();
+    project.scripts[&module_id].check(
+        crate::gdscript::check::CheckContext {
+            module_id: &module_id,
+            godot_project: &project,
+        },
+        &mut |error| errors.push(error),
+    );
+    assert!(
+        errors.iter().all(|error| matches!(
+            error,
+            GDError::RuleViolation {
+                module_id: error_module_id,
+                rule: Rule::InvalidInheritance,
+                ..
+            } if *error_module_id == module_id
+        )),
+        "{:?}",
+        errors
+    );
+    assert_eq!(
+        crate::gdscript::check::located(errors),
+        vec![(
+            String::from("B"),
+            String::from("Cyclic inheritance: A -> B -> A")
+        )]
+    );
+}
+
+#[test]
+fn autoloads() {
+    use crate::gdscript::{ast::Any, gd_type::Type, infer_type::InferTypeContext};
+    use crate::utils::slice::Slice;
+
+    let metadata: GDProjectMetadata = Slice::new(Rc::new(String::from(
+        "config_version=5\n\n[autoload]\n\nGameState=\"*res://game_state.gd\"\nHud=\"*res://hud.tscn\"\nLoader=\"res://loader.gd\"\n",
+    )))
+    .try_into()
+    .unwrap();
+    let autoloads = metadata.autoloads();
+    assert_eq!(
+        autoloads
+            .iter()
+            .map(|autoload| (
+                autoload.name.as_str(),
+                autoload.path.as_str(),
+                autoload.enabled
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            ("GameState", "res://game_state.gd", true),
+            ("Hud", "res://hud.tscn", true),
+            ("Loader", "res://loader.gd", false)
+        ]
+    );
+
+    let scene = "[gd_scene load_steps=2 format=3]\n\n[ext_resource type=\"Script\" path=\"res://hud.gd\" id=\"1_hud\"]\n\n[node name=\"Hud\" type=\"CanvasLayer\"]\nscript = ExtResource(\"1_hud\")\n\n[node name=\"Label\" type=\"Label\" parent=\".\"]\n";
+    assert_eq!(
+        SceneRoot::parse(scene),
+        Some(SceneRoot {
+            node_type: Some(String::from("CanvasLayer")),
+            script: Some(String::from("res://hud.gd")),
+        })
+    );
+
+    let sources = [
+        ("/project/game_state.gd", "var score = 0\n"),
+        ("/project/hud.gd", "class_name Hud\nextends CanvasLayer\n"),
+        (
+            "/project/main.gd",
+            "func f():\n\tvar a = GameState\n\tvar b = Hud\n\tvar c = Loader\n",
+        ),
+    ];
+    let mut project = GodotProject {
+        metadata,
+        ..GodotProject::for_test()
+    }
+    .with_scripts(&sources);
+    project
+        .script_index
+        .add_scene(Path::new("/project/hud.tscn"), Path::new("/project"), scene);
+
+    let main = &project.scripts[&ModuleID(Rc::new(String::from("/project/main.gd")))];
+    let types: Vec<Type> = main
+        .clone()
+        .upcast()
+        .descendants()
+        .into_iter()
+        .filter(|node| matches!(node.details(), Any::LocalIdentifier(_)))
+        .map(|node| {
+            node.try_recast::<crate::gdscript::ast::Expression>()
+                .unwrap()
+                .infer_type(InferTypeContext {
+                    godot_project: &project,
+                })
+        })
+        .collect();
+    let class = |name: &str| {
+        Type::Class {
+            name: Rc::new(name.to_owned()),
+        }
+        .non_null()
+    };
+    assert_eq!(
+        types,
+        vec![class("res://game_state.gd"), class("Hud"), Type::Poisoned]
+    );
+}
+
+#[test]
+fn global_classes() {
+    use crate::utils::slice::Slice;
+
+    let godot_3: GDProjectMetadata = Slice::new(Rc::new(String::from(
+        "_global_script_classes=[ {\n\"base\": \"Node\",\n\"class\": \"Enemy\",\n\"language\": \"GDScript\",\n\"path\": \"res://enemy.gd\"\n} ]\n",
+    )))
+    .try_into()
+    .unwrap();
+    let godot_4: GDProjectMetadata = Slice::new(Rc::new(String::from(
+        "list=Array[Dictionary]([{\n\"base\": &\"Node\",\n\"class\": &\"Enemy\",\n\"icon\": \"\",\n\"language\": &\"GDScript\",\n\"path\": \"res://enemy.gd\"\n}, {\n\"base\": &\"Node\",\n\"class\": &\"Ghost\",\n\"icon\": \"\",\n\"language\": &\"GDScript\",\n\"path\": \"res://ghost.gd\"\n}])\n",
+    )))
+    .try_into()
+    .unwrap();
+
+    let classes = godot_3.global_script_classes().unwrap();
+    assert_eq!(classes.len(), 1);
+    assert_eq!(classes[0].class.as_str(), "Enemy");
+    assert_eq!(classes[0].path.as_str(), "res://enemy.gd");
+
+    let sources = [
+        ("/project/enemy.gd", "class_name Enemy\nextends Node\n"),
+        ("/project/boss.gd", "class_name Boss\nextends Enemy\n"),
+    ];
+    let mut project = GodotProject::for_test().with_scripts(&sources);
+    project.script_index.global_classes = Some(GlobalClassRegistry {
+        source: ModuleID(Rc::new(String::from(
+            "/project/.godot/global_script_class_cache.cfg",
+        ))),
+        classes: godot_4.global_script_classes().unwrap(),
+    });
+
+    // Boss isn't registered, and Ghost isn't declared anymore
+    let mut errors = Vec::new();
+    project.check_global_classes(&mut |error| errors.push(error));
+    let errors: Vec<(String, String, String)> = errors
+        .into_iter()
+        .filter_map(|error| match error {
+            GDError::RuleViolation {
+                module_id,
+                src: Some(src),
+                message,
+                ..
+            } => Some((module_id.0.to_string(), src.as_str().to_owned(), message)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                String::from("/project/boss.gd"),
+                String::from("Boss"),
+                String::from("Class 'Boss' is missing from the global script class registry")
+            ),
+            (
+                String::from("/project/.godot/global_script_class_cache.cfg"),
+                String::from("Ghost"),
+                String::from(
+                    "Stale global script class 'Ghost': res://ghost.gd doesn't declare it"
+                )
+            ),
+        ]
+    );
+    assert!(project.class_exists("Ghost"));
 }
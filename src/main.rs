@@ -1,3 +1,4 @@
+mod extension_api;
 mod gdproject_metadata;
 mod gdscript;
 mod godot_project;
@@ -5,23 +6,74 @@ mod utils;
 
 use std::{collections::HashMap, convert::TryInto, env::current_dir, path::PathBuf, rc::Rc};
 
+use extension_api::ast::ExtensionAPI;
 use gdproject_metadata::ast::GDProjectMetadata;
-use godot_project::GodotProject;
-use utils::slice::Slice;
+use godot_project::{GlobalClassRegistry, GodotProject, ScriptIndex};
+use utils::{errors::GDError, slice::Slice};
 use walkdir::WalkDir;
 
 use crate::gdscript::{
     ast::ModuleID,
     check::{CheckContext, Checkable},
     parse::parse_script,
+    rules::{read_rule_config, read_toml_rule_config, RuleOptions},
 };
 
 fn main() -> Result<(), ()> {
     let files = find_files();
 
-    let project_code = std::fs::read_to_string(files.gdproject_metadata.unwrap()).unwrap();
+    let project_file = files.gdproject_metadata.unwrap();
+    let project_code = std::fs::read_to_string(&project_file).unwrap();
     let metadata: GDProjectMetadata = Slice::new(Rc::new(project_code)).try_into().unwrap();
 
+    // Godot 4 keeps the list of global classes out of project.godot
+    let global_classes = match files.global_class_cache {
+        Some(path) => {
+            let cache_code = std::fs::read_to_string(&path).unwrap();
+            let cache: GDProjectMetadata = Slice::new(Rc::new(cache_code)).try_into().unwrap();
+
+            cache
+                .global_script_classes()
+                .map(|classes| GlobalClassRegistry {
+                    source: ModuleID(Rc::new(path.to_string_lossy().to_string())),
+                    classes,
+                })
+        }
+        None => metadata
+            .global_script_classes()
+            .map(|classes| GlobalClassRegistry {
+                source: ModuleID(Rc::new(project_file.to_string_lossy().to_string())),
+                classes,
+            }),
+    };
+
+    let mut errors = Vec::new();
+
+    // rules can be configured in project.godot, and gdcheck.toml overrides
+    // whatever's configured there
+    let mut rule_severity = HashMap::new();
+    let mut rule_options = RuleOptions::default();
+
+    read_rule_config(
+        &metadata,
+        &ModuleID(Rc::new(project_file.to_string_lossy().to_string())),
+        &mut rule_severity,
+        &mut rule_options,
+        &mut |err| errors.push(err),
+    );
+
+    if let Some(path) = files.gdcheck_config {
+        let config_code = std::fs::read_to_string(&path).unwrap();
+
+        read_toml_rule_config(
+            Slice::new(Rc::new(config_code)),
+            &ModuleID(Rc::new(path.to_string_lossy().to_string())),
+            &mut rule_severity,
+            &mut rule_options,
+            &mut |err| errors.push(err),
+        );
+    }
+
     let mut scripts = HashMap::new();
 
     for script in files.gdscripts {
@@ -33,18 +85,54 @@ fn main() -> Result<(), ()> {
         scripts.insert(module_id, parsed);
     }
 
+    // projects can provide the API dump for the Godot version they target,
+    // otherwise (or if theirs can't be read) fall back to the one we ship
+    // with
+    let engine_api = match files.extension_api {
+        Some(path) => {
+            let api_code = std::fs::read_to_string(&path).unwrap();
+            let api: Result<ExtensionAPI, _> = Slice::new(Rc::new(api_code)).try_into();
+
+            match api {
+                Ok(api) => Rc::new(api),
+                Err(error) => {
+                    errors.push(GDError::ParseError {
+                        module_id: Some(ModuleID(Rc::new(path.to_string_lossy().to_string()))),
+                        src: error.src,
+                        message: error.message,
+                    });
+
+                    ExtensionAPI::bundled()
+                }
+            }
+        }
+        None => ExtensionAPI::bundled(),
+    };
+
+    // `res://` paths are relative to the directory containing project.godot
+    let project_root = project_file.parent().unwrap();
+    let mut script_index = ScriptIndex::new(&scripts, project_root);
+    script_index.global_classes = global_classes;
+
+    for scene in files.scenes {
+        let scene_code = std::fs::read_to_string(&scene).unwrap();
+
+        script_index.add_scene(&scene, project_root, &scene_code);
+    }
+
     let godot_project = GodotProject {
         metadata,
-        rule_severity: HashMap::new(),
+        rule_severity,
+        rule_options,
         scripts,
+        script_index,
+        engine_api,
     };
 
-    let mut errors = Vec::new();
-
     for (module_id, script) in godot_project.scripts.iter() {
         println!("Checking {}", module_id.0.as_str());
 
-        script.declarations.check(
+        script.check(
             CheckContext {
                 module_id,
                 godot_project: &godot_project,
@@ -53,6 +141,8 @@ fn main() -> Result<(), ()> {
         )
     }
 
+    godot_project.check_global_classes(&mut |err| errors.push(err));
+
     println!("\n\n{:?}", errors);
 
     Ok(())
@@ -60,7 +150,11 @@ fn main() -> Result<(), ()> {
 
 fn find_files() -> FoundFiles {
     let mut gdproject_metadata = None;
+    let mut extension_api = None;
+    let mut global_class_cache = None;
+    let mut gdcheck_config = None;
     let mut gdscripts = Vec::new();
+    let mut scenes = Vec::new();
 
     for entry in WalkDir::new(current_dir().unwrap()) {
         let entry = entry.unwrap();
@@ -72,6 +166,27 @@ fn find_files() -> FoundFiles {
             .unwrap_or(false)
         {
             gdproject_metadata = Some(entry.into_path());
+        } else if entry
+            .path()
+            .file_name()
+            .map(|name| name == "extension_api.json")
+            .unwrap_or(false)
+        {
+            extension_api = Some(entry.into_path());
+        } else if entry
+            .path()
+            .file_name()
+            .map(|name| name == "global_script_class_cache.cfg")
+            .unwrap_or(false)
+        {
+            global_class_cache = Some(entry.into_path());
+        } else if entry
+            .path()
+            .file_name()
+            .map(|name| name == "gdcheck.toml")
+            .unwrap_or(false)
+        {
+            gdcheck_config = Some(entry.into_path());
         } else if entry
             .path()
             .extension()
@@ -79,17 +194,32 @@ fn find_files() -> FoundFiles {
             .unwrap_or(false)
         {
             gdscripts.push(entry.into_path());
+        } else if entry
+            .path()
+            .extension()
+            .map(|ext| ext == "tscn")
+            .unwrap_or(false)
+        {
+            scenes.push(entry.into_path());
         }
     }
 
     FoundFiles {
         gdproject_metadata,
+        extension_api,
+        global_class_cache,
+        gdcheck_config,
         gdscripts,
+        scenes,
     }
 }
 
 #[derive(Debug, Clone)]
 struct FoundFiles {
     pub gdproject_metadata: Option<PathBuf>,
+    pub extension_api: Option<PathBuf>,
+    pub global_class_cache: Option<PathBuf>,
+    pub gdcheck_config: Option<PathBuf>,
     pub gdscripts: Vec<PathBuf>,
+    pub scenes: Vec<PathBuf>,
 }
@@ -3,7 +3,10 @@ use std::{fmt::Write, usize};
 use colored::Colorize;
 use enum_variant_type::EnumVariantType;
 
-use crate::gdscript::ast::ModuleID;
+use crate::gdscript::{
+    ast::ModuleID,
+    rules::{Rule, RuleSeverity},
+};
 
 use super::slice::Slice;
 
@@ -21,6 +24,23 @@ pub enum GDError {
         src: Option<Slice>,
         message: String,
     },
+    #[evt(derive(Debug, Clone, PartialEq))]
+    RuleViolation {
+        module_id: ModuleID,
+        src: Option<Slice>,
+        rule: Rule,
+        severity: RuleSeverity,
+        message: String,
+        fix: Option<Fix>,
+    },
+}
+
+/// An edit that resolves a diagnostic, by replacing `src` with
+/// `replacement`. Insertions replace an empty slice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub src: Slice,
+    pub replacement: String,
 }
 
 // impl GDError {
@@ -11,6 +11,7 @@ use self::slice::{Slicable, Slice};
 
 pub mod errors;
 pub mod slice;
+pub mod suggest;
 
 pub type ParseResult<T> = IResult<Slice, T, RawParseError>;
 
@@ -47,6 +47,15 @@ impl Slice {
         &self.full_string[self.start..self.end]
     }
 
+    /// The line and column this slice starts at, both counting from 1
+    pub fn line_and_column(&self) -> (usize, usize) {
+        let before = &self.full_string[..self.start];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|ch| *ch != '\n').count() + 1;
+
+        (line, column)
+    }
+
     pub fn slice_range(self, start: usize, end: Option<usize>) -> Slice {
         Self {
             full_string: self.full_string,
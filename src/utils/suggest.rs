@@ -0,0 +1,56 @@
+/// The candidate closest to `name`, if any is close enough to plausibly be
+/// what was meant, for "did you mean" hints on misspelled names. Ties go to
+/// whichever candidate comes first, so callers should list the likeliest
+/// names (like those in the nearest scopes) first.
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between `a` and `b`, ignoring case
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = usize::from(a_char != b_char);
+
+            current.push(
+                (previous[j] + substitution)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[test]
+fn suggestions() {
+    assert_eq!(edit_distance("velocity", "velocity"), 0);
+    assert_eq!(edit_distance("velocty", "velocity"), 1);
+    assert_eq!(edit_distance("Velocity", "velocity"), 0);
+    assert_eq!(edit_distance("", "abc"), 3);
+
+    let candidates = ["velocity", "position", "rotation"];
+    assert_eq!(did_you_mean("veloctiy", candidates), Some("velocity"));
+    assert_eq!(did_you_mean("rotaton", candidates), Some("rotation"));
+    assert_eq!(did_you_mean("health", candidates), None);
+}